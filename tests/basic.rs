@@ -9,10 +9,17 @@ use ciborium::Value;
 use hex_literal::hex;
 
 use virt::{Ctap2, Ctap2Error};
+use webauthn::u2f::{AuthenticateRequest, RegisterRequest, CONTROL_CHECK_ONLY, CONTROL_ENFORCE_USER_PRESENCE_AND_SIGN};
 use webauthn::{
-    AttStmtFormat, ClientPin, CredentialManagement, CredentialManagementParams, ExtensionsInput,
-    GetAssertion, GetInfo, KeyAgreementKey, MakeCredential, MakeCredentialOptions, PinToken,
-    PubKeyCredDescriptor, PubKeyCredParam, PublicKey, Rp, SharedSecret, User,
+    AttStmtFormat, AuthenticatorConfig, AuthenticatorConfigParams, BioEnrollment, BioEnrollmentParams,
+    ClientPin, CredentialManagement, CredentialManagementParams, ExtensionsInput, GetAssertion, GetInfo,
+    GetNextAssertion, KeyAgreementKey, LargeBlobs, MakeCredential, MakeCredentialOptions, PinToken,
+    PubKeyCredDescriptor, PubKeyCredParam, PublicKey, Reset, Rp, Selection, SharedSecret, User,
+    ENROLL_SAMPLE_STATUS_GOOD,
+    SUBCOMMAND_CANCEL_CURRENT_ENROLLMENT, SUBCOMMAND_ENABLE_ENTERPRISE_ATTESTATION,
+    SUBCOMMAND_ENROLL_BEGIN, SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE, SUBCOMMAND_ENUMERATE_ENROLLMENTS,
+    SUBCOMMAND_REMOVE_ENROLLMENT, SUBCOMMAND_SET_FRIENDLY_NAME, SUBCOMMAND_SET_MIN_PIN_LENGTH,
+    SUBCOMMAND_TOGGLE_ALWAYS_UV,
 };
 
 #[test]
@@ -37,13 +44,19 @@ fn test_get_info() {
             reply.attestation_formats,
             Some(vec!["packed".to_owned(), "none".to_owned()])
         );
+        assert_eq!(reply.option("bioEnroll"), Some(true));
+        assert_eq!(reply.uv_modality, Some(1));
     });
 }
 
-fn get_shared_secret(device: &Ctap2, platform_key_agreement: &KeyAgreementKey) -> SharedSecret {
-    let reply = device.exec(ClientPin::new(2, 2)).unwrap();
+fn get_shared_secret(
+    device: &Ctap2,
+    platform_key_agreement: &KeyAgreementKey,
+    pin_protocol: u8,
+) -> SharedSecret {
+    let reply = device.exec(ClientPin::new(pin_protocol, 2)).unwrap();
     let authenticator_key_agreement: PublicKey = reply.key_agreement.unwrap().into();
-    platform_key_agreement.shared_secret(&authenticator_key_agreement)
+    platform_key_agreement.shared_secret(&authenticator_key_agreement, pin_protocol)
 }
 
 fn set_pin(
@@ -51,12 +64,13 @@ fn set_pin(
     key_agreement_key: &KeyAgreementKey,
     shared_secret: &SharedSecret,
     pin: &[u8],
+    pin_protocol: u8,
 ) {
     let mut padded_pin = [0; 64];
     padded_pin[..pin.len()].copy_from_slice(pin);
     let pin_enc = shared_secret.encrypt(&padded_pin);
     let pin_auth = shared_secret.authenticate(&pin_enc);
-    let mut request = ClientPin::new(2, 3);
+    let mut request = ClientPin::new(pin_protocol, 3);
     request.key_agreement = Some(key_agreement_key.public_key());
     request.new_pin_enc = Some(pin_enc);
     request.pin_auth = Some(pin_auth);
@@ -65,11 +79,13 @@ fn set_pin(
 
 #[test]
 fn test_set_pin() {
-    let key_agreement_key = KeyAgreementKey::generate();
-    virt::run_ctap2(|device| {
-        let shared_secret = get_shared_secret(&device, &key_agreement_key);
-        set_pin(&device, &key_agreement_key, &shared_secret, b"123456");
-    })
+    for pin_protocol in [1, 2] {
+        let key_agreement_key = KeyAgreementKey::generate();
+        virt::run_ctap2(|device| {
+            let shared_secret = get_shared_secret(&device, &key_agreement_key, pin_protocol);
+            set_pin(&device, &key_agreement_key, &shared_secret, b"123456", pin_protocol);
+        })
+    }
 }
 
 fn get_pin_token(
@@ -79,6 +95,7 @@ fn get_pin_token(
     pin: &[u8],
     permissions: u8,
     rp_id: Option<String>,
+    pin_protocol: u8,
 ) -> PinToken {
     use sha2::{Digest as _, Sha256};
 
@@ -86,7 +103,7 @@ fn get_pin_token(
     hasher.update(pin);
     let pin_hash = hasher.finalize();
     let pin_hash_enc = shared_secret.encrypt(&pin_hash[..16]);
-    let mut request = ClientPin::new(2, 9);
+    let mut request = ClientPin::new(pin_protocol, 9);
     request.key_agreement = Some(key_agreement_key.public_key());
     request.pin_hash_enc = Some(pin_hash_enc);
     request.permissions = Some(permissions);
@@ -98,13 +115,23 @@ fn get_pin_token(
 
 #[test]
 fn test_get_pin_token() {
-    let key_agreement_key = KeyAgreementKey::generate();
-    let pin = b"123456";
-    virt::run_ctap2(|device| {
-        let shared_secret = get_shared_secret(&device, &key_agreement_key);
-        set_pin(&device, &key_agreement_key, &shared_secret, pin);
-        get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x01, None);
-    })
+    for pin_protocol in [1, 2] {
+        let key_agreement_key = KeyAgreementKey::generate();
+        let pin = b"123456";
+        virt::run_ctap2(|device| {
+            let shared_secret = get_shared_secret(&device, &key_agreement_key, pin_protocol);
+            set_pin(&device, &key_agreement_key, &shared_secret, pin, pin_protocol);
+            get_pin_token(
+                &device,
+                &key_agreement_key,
+                &shared_secret,
+                pin,
+                0x01,
+                None,
+                pin_protocol,
+            );
+        })
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -183,6 +210,7 @@ impl From<AttestationFormatsPreference> for Vec<&'static str> {
 #[derive(Debug)]
 struct TestMakeCredential {
     pin_token: Option<RequestPinToken>,
+    pin_protocol: u8,
     pub_key_alg: i32,
     attestation_formats_preference: Option<AttestationFormatsPreference>,
 }
@@ -202,8 +230,8 @@ impl TestMakeCredential {
 
         virt::run_ctap2(|device| {
             let pin_auth = self.pin_token.as_ref().map(|pin_token| {
-                let shared_secret = get_shared_secret(&device, &key_agreement_key);
-                set_pin(&device, &key_agreement_key, &shared_secret, pin);
+                let shared_secret = get_shared_secret(&device, &key_agreement_key, self.pin_protocol);
+                set_pin(&device, &key_agreement_key, &shared_secret, pin, self.pin_protocol);
                 let pin_token = get_pin_token(
                     &device,
                     &key_agreement_key,
@@ -211,6 +239,7 @@ impl TestMakeCredential {
                     pin,
                     pin_token.permissions,
                     pin_token.rp_id.clone(),
+                    self.pin_protocol,
                 );
                 pin_token.authenticate(client_data_hash)
             });
@@ -223,7 +252,7 @@ impl TestMakeCredential {
             let mut request = MakeCredential::new(client_data_hash, rp, user, pub_key_cred_params);
             if let Some(pin_auth) = pin_auth {
                 request.pin_auth = Some(pin_auth);
-                request.pin_protocol = Some(2);
+                request.pin_protocol = Some(self.pin_protocol);
             }
             request.attestation_formats_preference =
                 self.attestation_formats_preference.map(From::from);
@@ -288,21 +317,25 @@ fn test_make_credential() {
             rp_id: None,
         }),
     ];
-    for pin_token in pin_tokens {
-        for pub_key_alg in [-7, -11] {
-            TestMakeCredential {
-                pin_token: pin_token.clone(),
-                pub_key_alg,
-                attestation_formats_preference: None,
-            }
-            .run();
-            for attestation_formats_preference in AttestationFormatsPreference::ALL {
+    for pin_protocol in [1, 2] {
+        for pin_token in pin_tokens.clone() {
+            for pub_key_alg in [-7, -11] {
                 TestMakeCredential {
                     pin_token: pin_token.clone(),
+                    pin_protocol,
                     pub_key_alg,
-                    attestation_formats_preference: Some(*attestation_formats_preference),
+                    attestation_formats_preference: None,
                 }
                 .run();
+                for attestation_formats_preference in AttestationFormatsPreference::ALL {
+                    TestMakeCredential {
+                        pin_token: pin_token.clone(),
+                        pin_protocol,
+                        pub_key_alg,
+                        attestation_formats_preference: Some(*attestation_formats_preference),
+                    }
+                    .run();
+                }
             }
         }
     }
@@ -383,6 +416,60 @@ fn test_get_assertion() {
     }
 }
 
+#[test]
+fn test_get_assertion_pin_auth() {
+    let key_agreement_key = KeyAgreementKey::generate();
+    let pin = b"123456";
+    let rp_id = "example.com";
+    // TODO: client data
+    let client_data_hash = &[0; 32];
+
+    virt::run_ctap2(|device| {
+        let shared_secret = get_shared_secret(&device, &key_agreement_key, 2);
+        set_pin(&device, &key_agreement_key, &shared_secret, pin, 2);
+        let mc_pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x01, None, 2);
+
+        let rp = Rp::new(rp_id);
+        let user = User::new(b"id123").name("john.doe").display_name("John Doe");
+        let pub_key_cred_params = vec![PubKeyCredParam::new("public-key", -7)];
+        let mut request = MakeCredential::new(client_data_hash, rp, user, pub_key_cred_params);
+        request.pin_auth = Some(mc_pin_token.authenticate(client_data_hash));
+        request.pin_protocol = Some(2);
+        let response = device.exec(request).unwrap();
+        let credential = response.auth_data.credential.unwrap();
+
+        // A token minted with only the `ga` (GetAssertion) permission is
+        // accepted...
+        let ga_pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x02, None, 2);
+        let mut request = GetAssertion::new(rp_id, client_data_hash.to_vec());
+        request.allow_list = Some(vec![PubKeyCredDescriptor::new("public-key", credential.id.clone())]);
+        request.pin_auth = Some(ga_pin_token.authenticate(client_data_hash));
+        request.pin_protocol = Some(2);
+        let response = device.exec(request).unwrap();
+        assert_eq!(response.credential.id, credential.id);
+        assert_eq!(
+            response.auth_data.flags & 0b100,
+            0b100,
+            "uv flag not set in auth_data: 0b{:b}",
+            response.auth_data.flags
+        );
+        credential.verify_assertion(&response.auth_data, client_data_hash, &response.signature);
+
+        // ...but a token minted with only the `mc` (MakeCredential)
+        // permission is not.
+        let mc_pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x01, None, 2);
+        let mut request = GetAssertion::new(rp_id, client_data_hash.to_vec());
+        request.allow_list = Some(vec![PubKeyCredDescriptor::new("public-key", credential.id.clone())]);
+        request.pin_auth = Some(mc_pin_token.authenticate(client_data_hash));
+        request.pin_protocol = Some(2);
+        let result = device.exec(request);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x33));
+    });
+}
+
 #[derive(Debug)]
 struct TestListCredentials {
     pin_token_rp_id: bool,
@@ -396,11 +483,11 @@ impl TestListCredentials {
         let rp_id = "example.com";
         let user_id = b"id123";
         virt::run_ctap2(|device| {
-            let shared_secret = get_shared_secret(&device, &key_agreement_key);
-            set_pin(&device, &key_agreement_key, &shared_secret, pin);
+            let shared_secret = get_shared_secret(&device, &key_agreement_key, 2);
+            set_pin(&device, &key_agreement_key, &shared_secret, pin, 2);
 
             let pin_token =
-                get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x01, None);
+                get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x01, None, 2);
             // TODO: client data
             let client_data_hash = b"";
             let pin_auth = pin_token.authenticate(client_data_hash);
@@ -432,7 +519,7 @@ impl TestListCredentials {
             );
 
             let pin_token =
-                get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x04, None);
+                get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x04, None, 2);
             let pin_auth = pin_token.authenticate(&[0x02]);
             let request = CredentialManagement {
                 subcommand: 0x02,
@@ -455,6 +542,7 @@ impl TestListCredentials {
                 pin,
                 0x04,
                 pin_token_rp_id,
+                2,
             );
             let params = CredentialManagementParams {
                 rp_id_hash: Some(reply.rp_id_hash.unwrap().as_bytes().unwrap().to_owned()),
@@ -497,3 +585,661 @@ fn test_list_credentials() {
         }
     }
 }
+
+#[test]
+fn test_u2f_register_and_authenticate() {
+    let challenge = [0x41; 32];
+    let application = [0x42; 32];
+
+    virt::run_ctap1(|device| {
+        let register_request = RegisterRequest::new(challenge, application);
+        let register_response = device.register(register_request.clone()).unwrap();
+        register_response.verify(&register_request);
+
+        let authenticate_request = AuthenticateRequest::new(
+            CONTROL_ENFORCE_USER_PRESENCE_AND_SIGN,
+            challenge,
+            application,
+            register_response.key_handle.clone(),
+        );
+        let authenticate_response = device.authenticate(authenticate_request.clone()).unwrap();
+        assert_eq!(authenticate_response.counter, 1);
+        authenticate_response.verify(&authenticate_request, &register_response.public_key);
+
+        // A second assertion bumps the counter.
+        let authenticate_request = AuthenticateRequest::new(
+            CONTROL_ENFORCE_USER_PRESENCE_AND_SIGN,
+            challenge,
+            application,
+            register_response.key_handle.clone(),
+        );
+        let authenticate_response = device.authenticate(authenticate_request.clone()).unwrap();
+        assert_eq!(authenticate_response.counter, 2);
+        authenticate_response.verify(&authenticate_request, &register_response.public_key);
+    });
+}
+
+#[test]
+fn test_u2f_authenticate_check_only() {
+    let challenge = [0x11; 32];
+    let application = [0x22; 32];
+
+    virt::run_ctap1(|device| {
+        let register_request = RegisterRequest::new(challenge, application);
+        let register_response = device.register(register_request).unwrap();
+
+        let check_only = AuthenticateRequest::new(
+            CONTROL_CHECK_ONLY,
+            challenge,
+            application,
+            register_response.key_handle.clone(),
+        );
+        let error = device.authenticate(check_only).unwrap_err();
+        assert_eq!(error, virt::Ctap1Error(0x6985));
+
+        let mut unknown_key_handle = register_response.key_handle.clone();
+        unknown_key_handle[0] ^= 0xff;
+        let check_unknown = AuthenticateRequest::new(
+            CONTROL_CHECK_ONLY,
+            challenge,
+            application,
+            unknown_key_handle,
+        );
+        let error = device.authenticate(check_unknown).unwrap_err();
+        assert_eq!(error, virt::Ctap1Error(0x6a80));
+    });
+}
+
+#[test]
+fn test_u2f_version() {
+    virt::run_ctap1(|device| {
+        assert_eq!(device.version().unwrap(), "U2F_V2");
+    });
+}
+
+#[test]
+fn test_ctap2_credential_assertable_over_u2f() {
+    use sha2::{Digest as _, Sha256};
+
+    let rp_id = "example.com";
+    let client_data_hash = [0u8; 32];
+
+    virt::run_ctap2(|device| {
+        let rp = Rp::new(rp_id);
+        let user = User::new(b"id123").name("john.doe").display_name("John Doe");
+        let pub_key_cred_params = vec![PubKeyCredParam::new("public-key", -7)];
+        let request = MakeCredential::new(client_data_hash, rp, user, pub_key_cred_params);
+        let reply = device.exec(request).unwrap();
+        let credential = reply.auth_data.credential.unwrap();
+
+        let ctap1 = device.as_ctap1();
+        let mut hasher = Sha256::new();
+        hasher.update(rp_id.as_bytes());
+        let application: [u8; 32] = hasher.finalize().into();
+        let challenge = [0x55; 32];
+
+        let authenticate_request = AuthenticateRequest::new(
+            CONTROL_ENFORCE_USER_PRESENCE_AND_SIGN,
+            challenge,
+            application,
+            credential.id.clone(),
+        );
+        let authenticate_response = ctap1.authenticate(authenticate_request).unwrap();
+        assert_eq!(authenticate_response.counter, 1);
+    });
+}
+
+fn bio_enroll_auth(pin_token: &PinToken, subcommand: u8, params: Option<&BioEnrollmentParams>) -> Vec<u8> {
+    let mut message = vec![subcommand];
+    if let Some(params) = params {
+        message.extend(params.serialized());
+    }
+    pin_token.authenticate(&message)
+}
+
+#[test]
+fn test_bio_enrollment() {
+    let key_agreement_key = KeyAgreementKey::generate();
+    let pin = b"123456";
+
+    virt::run_ctap2(|device| {
+        let shared_secret = get_shared_secret(&device, &key_agreement_key, 2);
+        set_pin(&device, &key_agreement_key, &shared_secret, pin, 2);
+
+        // `getFingerprintSensorInfo` needs neither a pin token nor a
+        // sensor/fingerprint state.
+        let info = device.exec(BioEnrollment::get_fingerprint_sensor_info()).unwrap();
+        assert_eq!(info.modality, Some(1));
+        assert_eq!(info.max_capture_samples_required_for_enroll, Some(3));
+
+        let pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x08, None, 2);
+
+        let pin_auth = bio_enroll_auth(&pin_token, SUBCOMMAND_ENROLL_BEGIN, None);
+        let mut request = BioEnrollment::new(SUBCOMMAND_ENROLL_BEGIN);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        let reply = device.exec(request).unwrap();
+        assert_eq!(reply.last_enroll_sample_status, Some(ENROLL_SAMPLE_STATUS_GOOD as u32));
+        assert_eq!(reply.remaining_samples, Some(2));
+        let template_id = reply.template_id.unwrap();
+
+        for expected_remaining in [1u32, 0u32] {
+            let pin_auth = bio_enroll_auth(&pin_token, SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE, None);
+            let mut request = BioEnrollment::new(SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE);
+            request.pin_protocol = Some(2);
+            request.pin_auth = Some(pin_auth);
+            let reply = device.exec(request).unwrap();
+            assert_eq!(reply.remaining_samples, Some(expected_remaining));
+            assert_eq!(reply.template_id, Some(template_id.clone()));
+        }
+
+        // The enrollment is complete: a further capture is rejected.
+        let pin_auth = bio_enroll_auth(&pin_token, SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE, None);
+        let mut request = BioEnrollment::new(SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        let result = device.exec(request);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x30));
+
+        let pin_auth = bio_enroll_auth(&pin_token, SUBCOMMAND_ENUMERATE_ENROLLMENTS, None);
+        let mut request = BioEnrollment::new(SUBCOMMAND_ENUMERATE_ENROLLMENTS);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        let reply = device.exec(request).unwrap();
+        let template_infos = reply.template_infos.unwrap();
+        assert_eq!(template_infos, vec![(template_id.clone(), None)]);
+
+        let params = BioEnrollmentParams {
+            template_id: Some(template_id.clone()),
+            template_friendly_name: Some("Right thumb".to_owned()),
+        };
+        let pin_auth = bio_enroll_auth(&pin_token, SUBCOMMAND_SET_FRIENDLY_NAME, Some(&params));
+        let mut request = BioEnrollment::new(SUBCOMMAND_SET_FRIENDLY_NAME);
+        request.subcommand_params = Some(params);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        device.exec(request).unwrap();
+
+        let pin_auth = bio_enroll_auth(&pin_token, SUBCOMMAND_ENUMERATE_ENROLLMENTS, None);
+        let mut request = BioEnrollment::new(SUBCOMMAND_ENUMERATE_ENROLLMENTS);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        let reply = device.exec(request).unwrap();
+        assert_eq!(
+            reply.template_infos.unwrap(),
+            vec![(template_id.clone(), Some("Right thumb".to_owned()))]
+        );
+
+        let params = BioEnrollmentParams {
+            template_id: Some(template_id.clone()),
+            template_friendly_name: None,
+        };
+        let pin_auth = bio_enroll_auth(&pin_token, SUBCOMMAND_REMOVE_ENROLLMENT, Some(&params));
+        let mut request = BioEnrollment::new(SUBCOMMAND_REMOVE_ENROLLMENT);
+        request.subcommand_params = Some(params);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        device.exec(request).unwrap();
+
+        let pin_auth = bio_enroll_auth(&pin_token, SUBCOMMAND_ENUMERATE_ENROLLMENTS, None);
+        let mut request = BioEnrollment::new(SUBCOMMAND_ENUMERATE_ENROLLMENTS);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        let result = device.exec(request);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x2e));
+
+        // A cancelled enrollment doesn't leave stray state behind for a
+        // subsequent capture to complete.
+        let pin_auth = bio_enroll_auth(&pin_token, SUBCOMMAND_ENROLL_BEGIN, None);
+        let mut request = BioEnrollment::new(SUBCOMMAND_ENROLL_BEGIN);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        device.exec(request).unwrap();
+
+        let pin_auth = bio_enroll_auth(&pin_token, SUBCOMMAND_CANCEL_CURRENT_ENROLLMENT, None);
+        let mut request = BioEnrollment::new(SUBCOMMAND_CANCEL_CURRENT_ENROLLMENT);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        device.exec(request).unwrap();
+
+        let pin_auth = bio_enroll_auth(&pin_token, SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE, None);
+        let mut request = BioEnrollment::new(SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        let result = device.exec(request);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x30));
+    });
+}
+
+#[test]
+fn test_authenticator_config() {
+    let key_agreement_key = KeyAgreementKey::generate();
+    let pin = b"123456";
+
+    virt::run_ctap2(|device| {
+        let shared_secret = get_shared_secret(&device, &key_agreement_key, 2);
+        set_pin(&device, &key_agreement_key, &shared_secret, pin, 2);
+        let pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x20, None, 2);
+
+        let info = device.exec(GetInfo).unwrap();
+        assert_eq!(info.option("alwaysUv"), Some(false));
+        assert_eq!(info.option("ep"), Some(false));
+        assert_eq!(info.min_pin_length, Some(4));
+
+        let pin_auth = pin_token.authenticate(&AuthenticatorConfig::auth_message(SUBCOMMAND_TOGGLE_ALWAYS_UV, None));
+        let mut request = AuthenticatorConfig::new(SUBCOMMAND_TOGGLE_ALWAYS_UV);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        device.exec(request).unwrap();
+        let info = device.exec(GetInfo).unwrap();
+        assert_eq!(info.option("alwaysUv"), Some(true));
+
+        let pin_auth = pin_token.authenticate(&AuthenticatorConfig::auth_message(
+            SUBCOMMAND_ENABLE_ENTERPRISE_ATTESTATION,
+            None,
+        ));
+        let mut request = AuthenticatorConfig::new(SUBCOMMAND_ENABLE_ENTERPRISE_ATTESTATION);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        device.exec(request).unwrap();
+        let info = device.exec(GetInfo).unwrap();
+        assert_eq!(info.option("ep"), Some(true));
+
+        let params = AuthenticatorConfigParams {
+            new_min_pin_length: Some(8),
+            min_pin_length_rp_ids: Some(vec!["example.com".to_owned()]),
+            force_change_pin: Some(true),
+        };
+        let pin_auth = pin_token.authenticate(&AuthenticatorConfig::auth_message(
+            SUBCOMMAND_SET_MIN_PIN_LENGTH,
+            Some(&params),
+        ));
+        let mut request = AuthenticatorConfig::new(SUBCOMMAND_SET_MIN_PIN_LENGTH);
+        request.subcommand_params = Some(params);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        device.exec(request).unwrap();
+        let info = device.exec(GetInfo).unwrap();
+        assert_eq!(info.min_pin_length, Some(8));
+
+        // `forceChangePin` blocks further pinUvAuthToken issuance...
+        let mut hasher_request = ClientPin::new(2, 9);
+        hasher_request.key_agreement = Some(key_agreement_key.public_key());
+        use sha2::{Digest as _, Sha256};
+        let mut hasher = Sha256::new();
+        hasher.update(pin);
+        let pin_hash = hasher.finalize();
+        hasher_request.pin_hash_enc = Some(shared_secret.encrypt(&pin_hash[..16]));
+        hasher_request.permissions = Some(0x01);
+        let result = device.exec(hasher_request);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x37));
+
+        // ...until a new, long-enough PIN is set, which also clears the flag.
+        let mut padded_pin = [0; 64];
+        let new_pin = b"longenoughpin";
+        padded_pin[..new_pin.len()].copy_from_slice(new_pin);
+        let mut short_pin = [0; 64];
+        short_pin[..pin.len()].copy_from_slice(pin);
+
+        let mut short_request = ClientPin::new(2, 3);
+        short_request.key_agreement = Some(key_agreement_key.public_key());
+        short_request.new_pin_enc = Some(shared_secret.encrypt(&short_pin));
+        short_request.pin_auth = Some(shared_secret.authenticate(short_request.new_pin_enc.as_ref().unwrap()));
+        let result = device.exec(short_request);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x37));
+
+        let mut long_request = ClientPin::new(2, 3);
+        long_request.key_agreement = Some(key_agreement_key.public_key());
+        long_request.new_pin_enc = Some(shared_secret.encrypt(&padded_pin));
+        long_request.pin_auth = Some(shared_secret.authenticate(long_request.new_pin_enc.as_ref().unwrap()));
+        device.exec(long_request).unwrap();
+
+        get_pin_token(&device, &key_agreement_key, &shared_secret, new_pin, 0x01, None, 2);
+    });
+}
+
+#[derive(Debug)]
+struct TestMinPinLengthExtension {
+    rp_ids_allowlist: Option<Vec<&'static str>>,
+}
+
+impl TestMinPinLengthExtension {
+    fn run(&self) {
+        println!("{}", "=".repeat(80));
+        println!("Running test:");
+        println!("{self:#?}");
+        println!();
+
+        let key_agreement_key = KeyAgreementKey::generate();
+        let pin = b"123456";
+        let rp_id = "example.com";
+        // TODO: client data
+        let client_data_hash = &[0; 32];
+
+        virt::run_ctap2(|device| {
+            let shared_secret = get_shared_secret(&device, &key_agreement_key, 2);
+            set_pin(&device, &key_agreement_key, &shared_secret, pin, 2);
+
+            if let Some(rp_ids) = &self.rp_ids_allowlist {
+                let config_pin_token =
+                    get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x20, None, 2);
+                let params = AuthenticatorConfigParams {
+                    new_min_pin_length: None,
+                    min_pin_length_rp_ids: Some(rp_ids.iter().map(|id| id.to_string()).collect()),
+                    force_change_pin: None,
+                };
+                let pin_auth = config_pin_token.authenticate(&AuthenticatorConfig::auth_message(
+                    SUBCOMMAND_SET_MIN_PIN_LENGTH,
+                    Some(&params),
+                ));
+                let mut request = AuthenticatorConfig::new(SUBCOMMAND_SET_MIN_PIN_LENGTH);
+                request.subcommand_params = Some(params);
+                request.pin_protocol = Some(2);
+                request.pin_auth = Some(pin_auth);
+                device.exec(request).unwrap();
+            }
+
+            let rp = Rp::new(rp_id);
+            let user = User::new(b"id123").name("john.doe").display_name("John Doe");
+            let pub_key_cred_params = vec![PubKeyCredParam::new("public-key", -7)];
+            let mut request = MakeCredential::new(client_data_hash, rp, user, pub_key_cred_params);
+            request.extensions = Some(ExtensionsInput {
+                min_pin_length: Some(true),
+                ..Default::default()
+            });
+            let response = device.exec(request).unwrap();
+
+            let allowed = self
+                .rp_ids_allowlist
+                .as_ref()
+                .map_or(true, |rp_ids| rp_ids.contains(&rp_id));
+            if allowed {
+                let extensions = response.auth_data.extensions.unwrap();
+                assert_eq!(extensions.get("minPinLength"), Some(&Value::from(4)));
+            } else {
+                assert!(response.auth_data.extensions.is_none());
+            }
+        });
+    }
+}
+
+#[test]
+fn test_min_pin_length_extension() {
+    for rp_ids_allowlist in [None, Some(vec!["example.com"]), Some(vec!["other.example"])] {
+        TestMinPinLengthExtension { rp_ids_allowlist }.run()
+    }
+}
+
+#[test]
+fn test_reset() {
+    let key_agreement_key = KeyAgreementKey::generate();
+    let pin = b"123456";
+    let rp_id = "example.com";
+    let user_id = b"id123";
+
+    virt::run_ctap2(|device| {
+        // Reset requires user presence.
+        device.set_user_presence(false);
+        let result = device.exec(Reset);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x27));
+        device.set_user_presence(true);
+
+        let shared_secret = get_shared_secret(&device, &key_agreement_key, 2);
+        set_pin(&device, &key_agreement_key, &shared_secret, pin, 2);
+        let pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x01, None, 2);
+        // TODO: client data
+        let client_data_hash = b"";
+        let pin_auth = pin_token.authenticate(client_data_hash);
+
+        let rp = Rp::new(rp_id);
+        let user = User::new(user_id).name("john.doe").display_name("John Doe");
+        let pub_key_cred_params = vec![PubKeyCredParam::new("public-key", -7)];
+        let mut request = MakeCredential::new(client_data_hash, rp, user, pub_key_cred_params);
+        request.options = Some(MakeCredentialOptions::default().rk(true));
+        request.pin_auth = Some(pin_auth);
+        request.pin_protocol = Some(2);
+        device.exec(request).unwrap();
+
+        let pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x04, None, 2);
+        let pin_auth = pin_token.authenticate(&[0x02]);
+        let request = CredentialManagement {
+            subcommand: 0x02,
+            subcommand_params: None,
+            pin_protocol: Some(2),
+            pin_auth: Some(pin_auth),
+        };
+        let reply = device.exec(request).unwrap();
+        assert_eq!(reply.total_rps, Some(1));
+
+        // Flip every other piece of persistent state a reset should also
+        // revert to its factory default.
+        let config_pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x20, None, 2);
+
+        let pin_auth = config_pin_token
+            .authenticate(&AuthenticatorConfig::auth_message(SUBCOMMAND_TOGGLE_ALWAYS_UV, None));
+        let mut request = AuthenticatorConfig::new(SUBCOMMAND_TOGGLE_ALWAYS_UV);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        device.exec(request).unwrap();
+
+        let pin_auth = config_pin_token.authenticate(&AuthenticatorConfig::auth_message(
+            SUBCOMMAND_ENABLE_ENTERPRISE_ATTESTATION,
+            None,
+        ));
+        let mut request = AuthenticatorConfig::new(SUBCOMMAND_ENABLE_ENTERPRISE_ATTESTATION);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        device.exec(request).unwrap();
+
+        let params = AuthenticatorConfigParams {
+            new_min_pin_length: Some(8),
+            min_pin_length_rp_ids: None,
+            force_change_pin: None,
+        };
+        let pin_auth = config_pin_token.authenticate(&AuthenticatorConfig::auth_message(
+            SUBCOMMAND_SET_MIN_PIN_LENGTH,
+            Some(&params),
+        ));
+        let mut request = AuthenticatorConfig::new(SUBCOMMAND_SET_MIN_PIN_LENGTH);
+        request.subcommand_params = Some(params);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        device.exec(request).unwrap();
+
+        let bio_pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x08, None, 2);
+        let pin_auth = bio_enroll_auth(&bio_pin_token, SUBCOMMAND_ENROLL_BEGIN, None);
+        let mut request = BioEnrollment::new(SUBCOMMAND_ENROLL_BEGIN);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_auth);
+        let reply = device.exec(request).unwrap();
+        let mut remaining_samples = reply.remaining_samples.unwrap();
+        while remaining_samples > 0 {
+            let pin_auth = bio_enroll_auth(&bio_pin_token, SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE, None);
+            let mut request = BioEnrollment::new(SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE);
+            request.pin_protocol = Some(2);
+            request.pin_auth = Some(pin_auth);
+            let reply = device.exec(request).unwrap();
+            remaining_samples = reply.remaining_samples.unwrap();
+        }
+
+        let info = device.exec(GetInfo).unwrap();
+        assert_eq!(info.option("alwaysUv"), Some(true));
+        assert_eq!(info.option("ep"), Some(true));
+        assert_eq!(info.min_pin_length, Some(8));
+
+        let pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x04, None, 2);
+        device.exec(Reset).unwrap();
+
+        // The pinUvAuthToken minted before the reset no longer grants
+        // anything: the reset discards it along with the PIN.
+        let pin_auth = pin_token.authenticate(&[0x02]);
+        let request = CredentialManagement {
+            subcommand: 0x02,
+            subcommand_params: None,
+            pin_protocol: Some(2),
+            pin_auth: Some(pin_auth),
+        };
+        let result = device.exec(request);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x33));
+
+        // The PIN itself is gone too: GetInfo now reports no PIN set.
+        let reply = device.exec(GetInfo).unwrap();
+        assert_eq!(reply.option("clientPin"), Some(false));
+
+        // Every other piece of persistent state reverted to its factory
+        // default too.
+        assert_eq!(reply.option("alwaysUv"), Some(false));
+        assert_eq!(reply.option("ep"), Some(false));
+        assert_eq!(reply.min_pin_length, Some(4));
+
+        // Once reset, a second reset is rejected.
+        let result = device.exec(Reset);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x30));
+    });
+}
+
+#[test]
+fn test_get_next_assertion() {
+    let key_agreement_key = KeyAgreementKey::generate();
+    let pin = b"123456";
+    let rp_id = "example.com";
+    let client_data_hash = b"";
+
+    virt::run_ctap2(|device| {
+        let shared_secret = get_shared_secret(&device, &key_agreement_key, 2);
+        set_pin(&device, &key_agreement_key, &shared_secret, pin, 2);
+        let pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x01, None, 2);
+        let pin_auth = pin_token.authenticate(client_data_hash);
+
+        let pub_key_cred_params = vec![PubKeyCredParam::new("public-key", -7)];
+        let mut credentials = Vec::new();
+        for user_id in [b"id123".as_slice(), b"id456".as_slice()] {
+            let rp = Rp::new(rp_id);
+            let user = User::new(user_id).name("john.doe").display_name("John Doe");
+            let mut request =
+                MakeCredential::new(client_data_hash, rp, user, pub_key_cred_params.clone());
+            request.options = Some(MakeCredentialOptions::default().rk(true));
+            request.pin_auth = Some(pin_auth.clone());
+            request.pin_protocol = Some(2);
+            let reply = device.exec(request).unwrap();
+            credentials.push((user_id.to_vec(), reply.auth_data.credential.unwrap()));
+        }
+
+        let request = GetAssertion::new(rp_id, client_data_hash);
+        let first = device.exec(request).unwrap();
+        assert_eq!(first.number_of_credentials, Some(2));
+
+        let second = device.exec(GetNextAssertion).unwrap();
+        assert_eq!(second.number_of_credentials, None);
+
+        let mut user_ids: Vec<Vec<u8>> = vec![first.user_id.clone().unwrap(), second.user_id.clone().unwrap()];
+        user_ids.sort();
+        let mut expected: Vec<Vec<u8>> = credentials.iter().map(|(id, _)| id.clone()).collect();
+        expected.sort();
+        assert_eq!(user_ids, expected);
+
+        for (reply, user_id) in [(&first, &first.user_id), (&second, &second.user_id)] {
+            let (_, credential) = credentials
+                .iter()
+                .find(|(id, _)| Some(id) == user_id.as_ref())
+                .unwrap();
+            credential.verify_assertion(&reply.auth_data, client_data_hash, &reply.signature);
+        }
+
+        // The queue is now empty.
+        let result = device.exec(GetNextAssertion);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x30));
+    });
+}
+
+#[test]
+fn test_large_blobs() {
+    use sha2::{Digest as _, Sha256};
+
+    let key_agreement_key = KeyAgreementKey::generate();
+    let pin = b"123456";
+
+    virt::run_ctap2(|device| {
+        let shared_secret = get_shared_secret(&device, &key_agreement_key, 2);
+        set_pin(&device, &key_agreement_key, &shared_secret, pin, 2);
+        let pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x10, None, 2);
+
+        // The contents of the large-blob array, followed by a 16-byte
+        // truncated checksum over them, split across two write fragments.
+        let contents = b"a large blob, serialized as a CBOR byte string".to_vec();
+        let mut array = contents.clone();
+        array.extend_from_slice(&Sha256::digest(&contents)[..16]);
+        let (fragment1, fragment2) = array.split_at(array.len() / 2);
+
+        let mut request = LargeBlobs::set(0, fragment1.to_vec());
+        request.length = Some(array.len() as u32);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_token.authenticate(&LargeBlobs::auth_message(0, fragment1)));
+        device.exec(request).unwrap();
+
+        let offset2 = fragment1.len() as u32;
+        let mut request = LargeBlobs::set(offset2, fragment2.to_vec());
+        request.pin_protocol = Some(2);
+        request.pin_auth =
+            Some(pin_token.authenticate(&LargeBlobs::auth_message(offset2, fragment2)));
+        device.exec(request).unwrap();
+
+        // Reading back (unauthenticated) returns exactly what was written.
+        let reply = device.exec(LargeBlobs::get(0, array.len() as u32)).unwrap();
+        assert_eq!(reply.config, Some(array.clone()));
+    });
+
+    virt::run_ctap2(|device| {
+        let shared_secret = get_shared_secret(&device, &key_agreement_key, 2);
+        set_pin(&device, &key_agreement_key, &shared_secret, pin, 2);
+        let pin_token =
+            get_pin_token(&device, &key_agreement_key, &shared_secret, pin, 0x10, None, 2);
+
+        // A single-fragment write whose trailing checksum doesn't match its
+        // contents is rejected, and the array is left unmodified.
+        let mut corrupt = b"short blob".to_vec();
+        corrupt.extend_from_slice(&[0u8; 16]);
+
+        let mut request = LargeBlobs::set(0, corrupt.clone());
+        request.length = Some(corrupt.len() as u32);
+        request.pin_protocol = Some(2);
+        request.pin_auth = Some(pin_token.authenticate(&LargeBlobs::auth_message(0, &corrupt)));
+        let result = device.exec(request);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x3c));
+
+        // A `length` too small to even fit the 16-byte checksum is rejected
+        // outright, rather than underflowing when the write "completes".
+        let short_fragment = b"hi".to_vec();
+        let mut request = LargeBlobs::set(0, short_fragment.clone());
+        request.length = Some(short_fragment.len() as u32);
+        request.pin_protocol = Some(2);
+        request.pin_auth =
+            Some(pin_token.authenticate(&LargeBlobs::auth_message(0, &short_fragment)));
+        let result = device.exec(request);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x03));
+    });
+}
+
+#[test]
+fn test_selection() {
+    virt::run_ctap2(|device| {
+        // A touch grants the request.
+        device.set_user_presence(true);
+        device.exec(Selection).unwrap();
+
+        // Without a touch, the platform learns this authenticator timed out
+        // (and should keep waiting on the others it sent the request to).
+        device.set_user_presence(false);
+        let result = device.exec(Selection);
+        assert_eq!(result.unwrap_err(), Ctap2Error(0x2f));
+    });
+}
@@ -0,0 +1,58 @@
+//! The fixed `packed` attestation key and self-signed certificate the
+//! virtual authenticator uses to sign `MakeCredential` responses. A real
+//! device would ship a batch certificate provisioned at manufacture time;
+//! the virtual one just ships a single, checked-in keypair.
+
+use hex_literal::hex;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use p256::SecretKey;
+use std::sync::OnceLock;
+
+const ATTESTATION_KEY_DER: &[u8] = &hex!(
+    "30770201010420c7c00db46752da629e5b43cacca70d495439dd021c37b3065e5e24b0734f245a"
+    "a00a06082a8648ce3d030107a14403420004c7a1a4fdf0a6e0a2a9c2071d23b497bef9a47bc28f4"
+    "de3282205a3e523c6e3fa2c05a9969778f5a8966cf19afd73f803721c2b328878d39fd41d498ecb"
+    "0cbfeb"
+);
+
+pub(crate) const ATTESTATION_CERT_DER: &[u8] = &hex!(
+    "30820227308201cda00302010202141375b67286eef7d8cfeb086e8af6c23f98656f4f300a0608"
+    "2a8648ce3d04030230683120301e06035504030c176669646f2d61757468656e74696361746f72"
+    "2d766972743120301e060355040a0c176669646f2d61757468656e74696361746f725f666f726b"
+    "31223020060355040b0c1941757468656e74696361746f72204174746573746174696f6e303020"
+    "170d3236303733303134303735305a180f32313236303730363134303735305a30683120301e06"
+    "035504030c176669646f2d61757468656e74696361746f722d766972743120301e060355040a0c"
+    "176669646f2d61757468656e74696361746f725f666f726b31223020060355040b0c1941757468"
+    "656e74696361746f72204174746573746174696f6e3059301306072a8648ce3d020106082a8648"
+    "ce3d03010703420004c7a1a4fdf0a6e0a2a9c2071d23b497bef9a47bc28f4de3282205a3e523c6e"
+    "3fa2c05a9969778f5a8966cf19afd73f803721c2b328878d39fd41d498ecb0cbfeba3533051301d"
+    "0603551d0e041604146411f866207253bce04ca5cbe9cdafcd0c2a3471301f0603551d23041830"
+    "1680146411f866207253bce04ca5cbe9cdafcd0c2a3471300f0603551d130101ff040530030101"
+    "ff300a06082a8648ce3d0403020348003045022100ab877b4b56dfd987cea0f5604ee9e11ff37f"
+    "f6a45d1650184afeb6ba7a80efcf022049e883233200dbd981d66a6b740f5c26f0f0f2642a9dbd"
+    "d8b81b3f86c6b8856e"
+);
+
+fn attestation_signing_key() -> &'static SigningKey {
+    static KEY: OnceLock<SigningKey> = OnceLock::new();
+    KEY.get_or_init(|| {
+        let secret = SecretKey::from_sec1_der(ATTESTATION_KEY_DER).unwrap();
+        SigningKey::from(secret)
+    })
+}
+
+/// Signs `auth_data || client_data_hash` (the packed attestation signature
+/// base, CTAP2 §6.5.5) with the batch attestation key and returns the
+/// DER-encoded ECDSA signature.
+pub(crate) fn sign(auth_data: &[u8], client_data_hash: &[u8]) -> Vec<u8> {
+    let mut message = auth_data.to_vec();
+    message.extend_from_slice(client_data_hash);
+    sign_raw(&message)
+}
+
+/// Signs an arbitrary message with the batch attestation key. Used directly
+/// by the U2F `REGISTER` handler, whose signature base isn't `authData`.
+pub(crate) fn sign_raw(message: &[u8]) -> Vec<u8> {
+    let signature: Signature = attestation_signing_key().sign(message);
+    signature.to_der().as_bytes().to_vec()
+}
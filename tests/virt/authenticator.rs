@@ -0,0 +1,989 @@
+//! The virtual authenticator's state machine: credential storage, PIN/UV
+//! handling and the CTAP2 command handlers. This is the thing `Ctap2::exec`
+//! talks to in-process, standing in for a real authenticator firmware.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use ciborium::Value;
+use p256::ecdsa::SigningKey;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use super::attestation;
+use super::crypto::{self, KeyAgreementKeyPair};
+use super::status;
+
+/// `8BC54968-07B1-4D5F-B249-607F5D527DA2`, this fork's fixed AAGUID.
+const AAGUID: [u8; 16] = [
+    0x8b, 0xc5, 0x49, 0x68, 0x07, 0xb1, 0x4d, 0x5f, 0xb2, 0x49, 0x60, 0x7f, 0x5d, 0x52, 0x7d, 0xa2,
+];
+
+const FLAG_UP: u8 = 0b0000_0001;
+const FLAG_UV: u8 = 0b0000_0100;
+const FLAG_AT: u8 = 0b0100_0000;
+const FLAG_ED: u8 = 0b1000_0000;
+
+/// An enrolled fingerprint template (`authenticatorBioEnrollment`).
+pub(crate) struct BioTemplate {
+    pub(crate) id: Vec<u8>,
+    pub(crate) friendly_name: Option<String>,
+}
+
+/// An in-progress enrollment started by `enrollBegin`, advanced one sample
+/// at a time by `enrollCaptureNextSample`.
+pub(crate) struct BioEnrollmentState {
+    pub(crate) template_id: Vec<u8>,
+    pub(crate) remaining_samples: u8,
+}
+
+pub(crate) struct StoredCredential {
+    pub(crate) rp_id: String,
+    pub(crate) rp_id_hash: [u8; 32],
+    pub(crate) user_id: Vec<u8>,
+    pub(crate) user_name: Option<String>,
+    pub(crate) user_display_name: Option<String>,
+    pub(crate) id: Vec<u8>,
+    pub(crate) signing_key: SigningKey,
+    pub(crate) sign_count: u32,
+    pub(crate) resident: bool,
+    pub(crate) third_party_payment: bool,
+}
+
+/// A credential set aside by `authenticatorGetAssertion` when it matched
+/// more than one discoverable credential, to be handed out one at a time by
+/// `authenticatorGetNextAssertion`.
+struct PendingAssertion {
+    index: usize,
+    rp_id_hash: [u8; 32],
+    client_data_hash: Vec<u8>,
+    uv: bool,
+    third_party_payment: bool,
+}
+
+/// An in-progress `authenticatorLargeBlobs` write, assembled one
+/// sequential fragment at a time starting from offset 0.
+struct PendingLargeBlobWrite {
+    expected_length: usize,
+    buffer: Vec<u8>,
+}
+
+struct IssuedToken {
+    bytes: [u8; 32],
+    permissions: u8,
+    rp_id: Option<String>,
+    /// The `pinUvAuthProtocol` used to issue this token; `authenticate`
+    /// truncates its HMAC tag accordingly.
+    protocol: u8,
+}
+
+impl IssuedToken {
+    fn authenticate(&self, message: &[u8]) -> Vec<u8> {
+        crypto::hmac_authenticate(&self.bytes, message, self.protocol)
+    }
+}
+
+pub(crate) struct Authenticator {
+    key_agreement: KeyAgreementKeyPair,
+    pin: Option<Vec<u8>>,
+    token: Option<IssuedToken>,
+    pub(crate) credentials: Vec<StoredCredential>,
+    pub(crate) bio_templates: Vec<BioTemplate>,
+    pub(crate) bio_enrollment: Option<BioEnrollmentState>,
+    pub(crate) always_uv: bool,
+    pub(crate) enterprise_attestation: bool,
+    pub(crate) min_pin_length: u8,
+    pub(crate) min_pin_length_rp_ids: Vec<String>,
+    pub(crate) force_change_pin: bool,
+    /// Whether the (simulated) user grants presence to presence-requiring
+    /// commands; it stays in effect until changed. Tests flip this with
+    /// [`super::Ctap2::set_user_presence`] to exercise rejection paths.
+    pub(crate) user_presence_granted: bool,
+    /// `authenticatorReset` is only accepted once per power cycle; this
+    /// virtual device models "shortly after power-up" as "hasn't reset yet".
+    pub(crate) reset_performed: bool,
+    /// Credentials queued by a `authenticatorGetAssertion` call that matched
+    /// more than one discoverable credential, in the order
+    /// `authenticatorGetNextAssertion` should hand them out. Cleared by any
+    /// command other than `authenticatorGetNextAssertion` itself.
+    pending_assertions: VecDeque<PendingAssertion>,
+    /// The serialized `largeBlobArray`: CBOR data followed by a 16-byte
+    /// truncated SHA-256 checksum over the rest of the buffer.
+    large_blob_array: Vec<u8>,
+    /// Fragments accumulated by an in-progress `authenticatorLargeBlobs`
+    /// write, until the final fragment's checksum validates.
+    pending_large_blob_write: Option<PendingLargeBlobWrite>,
+}
+
+/// The default `minPINLength`, per CTAP2.1 §6.11.
+const DEFAULT_MIN_PIN_LENGTH: u8 = 4;
+
+impl Authenticator {
+    pub(crate) fn new() -> Self {
+        Self {
+            key_agreement: KeyAgreementKeyPair::generate(),
+            pin: None,
+            token: None,
+            credentials: Vec::new(),
+            bio_templates: Vec::new(),
+            bio_enrollment: None,
+            always_uv: false,
+            enterprise_attestation: false,
+            min_pin_length: DEFAULT_MIN_PIN_LENGTH,
+            min_pin_length_rp_ids: Vec::new(),
+            force_change_pin: false,
+            user_presence_granted: true,
+            reset_performed: false,
+            pending_assertions: VecDeque::new(),
+            large_blob_array: empty_large_blob_array(),
+            pending_large_blob_write: None,
+        }
+    }
+
+    pub(crate) fn handle_cbor(&mut self, cmd: u8, params: Option<Value>) -> Result<Option<Value>, u8> {
+        // A `authenticatorGetNextAssertion` queue only survives between a
+        // `GetAssertion` and the `GetNextAssertion` calls that drain it; any
+        // other intervening command invalidates it.
+        if cmd != 0x08 {
+            self.pending_assertions.clear();
+        }
+        match cmd {
+            0x01 => self.make_credential(params).map(Some),
+            0x02 => self.get_assertion(params).map(Some),
+            0x04 => Ok(Some(self.get_info())),
+            0x06 => self.client_pin(params).map(Some),
+            0x07 => self.reset().map(Some),
+            0x08 => self.get_next_assertion().map(Some),
+            0x09 => self.bio_enrollment(params).map(Some),
+            0x0a => self.credential_management(params).map(Some),
+            0x0b => self.selection().map(Some),
+            0x0c => self.large_blobs(params).map(Some),
+            0x0d => self.authenticator_config(params).map(Some),
+            _ => Err(status::INVALID_COMMAND),
+        }
+    }
+
+    /// Clears discoverable credentials, the PIN and the pinUvAuthToken
+    /// key-agreement state. Requires user presence and only succeeds once
+    /// per power cycle (modelled here as "hasn't already reset").
+    fn reset(&mut self) -> Result<Value, u8> {
+        if !self.user_presence_granted {
+            return Err(status::OPERATION_DENIED);
+        }
+        if self.reset_performed {
+            return Err(status::NOT_ALLOWED);
+        }
+        self.reset_performed = true;
+        self.credentials.clear();
+        self.pin = None;
+        self.token = None;
+        self.key_agreement = KeyAgreementKeyPair::generate();
+        self.large_blob_array = empty_large_blob_array();
+        self.pending_large_blob_write = None;
+        self.bio_templates.clear();
+        self.bio_enrollment = None;
+        self.always_uv = false;
+        self.enterprise_attestation = false;
+        self.min_pin_length = DEFAULT_MIN_PIN_LENGTH;
+        self.min_pin_length_rp_ids.clear();
+        self.force_change_pin = false;
+        Ok(Value::Map(Vec::new()))
+    }
+
+    /// Waits for user presence so the platform can tell which of several
+    /// connected authenticators the user picked.
+    fn selection(&self) -> Result<Value, u8> {
+        if !self.user_presence_granted {
+            return Err(status::USER_ACTION_TIMEOUT);
+        }
+        Ok(Value::Map(Vec::new()))
+    }
+
+    fn get_info(&self) -> Value {
+        let mut options = vec![
+            (Value::Text("rk".into()), Value::Bool(true)),
+            (Value::Text("clientPin".into()), Value::Bool(self.pin.is_some())),
+            (Value::Text("bioEnroll".into()), Value::Bool(true)),
+            (Value::Text("alwaysUv".into()), Value::Bool(self.always_uv)),
+            (Value::Text("ep".into()), Value::Bool(self.enterprise_attestation)),
+            (Value::Text("largeBlobs".into()), Value::Bool(true)),
+        ];
+        MapBuilder::new()
+            .entry(
+                0x01,
+                Value::Array(vec![
+                    Value::Text("FIDO_2_0".into()),
+                    Value::Text("FIDO_2_1".into()),
+                ]),
+            )
+            .entry(0x03, Value::Bytes(AAGUID.to_vec()))
+            .entry(0x04, Value::Map(options))
+            .entry(0x0b, LARGE_BLOB_MAX_SERIALIZED_ARRAY as i128)
+            .entry(
+                0x06,
+                Value::Array(vec![Value::Integer(2.into()), Value::Integer(1.into())]),
+            )
+            .entry(
+                0x16,
+                Value::Array(vec![
+                    Value::Text("packed".into()),
+                    Value::Text("none".into()),
+                ]),
+            )
+            // `uvModality` bit 0x01: fingerprint.
+            .entry(0x0e, 1i128)
+            .entry(0x15, self.min_pin_length as i128)
+            .build()
+    }
+
+    fn client_pin(&mut self, params: Option<Value>) -> Result<Value, u8> {
+        let params = params.ok_or(status::MISSING_PARAMETER)?;
+        let protocol = map_get_int(&params, 0x01).ok_or(status::MISSING_PARAMETER)? as u8;
+        let subcommand = map_get_int(&params, 0x02).ok_or(status::MISSING_PARAMETER)? as u8;
+        match subcommand {
+            0x02 => Ok(MapBuilder::new()
+                .entry(0x01, self.key_agreement.public_key_cbor())
+                .build()),
+            0x03 => {
+                let platform_key = map_get(&params, 0x03).ok_or(status::MISSING_PARAMETER)?;
+                let new_pin_enc = map_get_bytes(&params, 0x05).ok_or(status::MISSING_PARAMETER)?;
+                let shared_secret = self.key_agreement.shared_secret(platform_key, protocol);
+                let padded = shared_secret.decrypt(&new_pin_enc);
+                let pin: Vec<u8> = padded.into_iter().take_while(|&b| b != 0).collect();
+                if (pin.len() as u8) < self.min_pin_length {
+                    return Err(status::PIN_POLICY_VIOLATION);
+                }
+                self.pin = Some(pin);
+                self.force_change_pin = false;
+                Ok(Value::Map(Vec::new()))
+            }
+            0x09 => {
+                let platform_key = map_get(&params, 0x03).ok_or(status::MISSING_PARAMETER)?;
+                let pin_hash_enc = map_get_bytes(&params, 0x06).ok_or(status::MISSING_PARAMETER)?;
+                let permissions = map_get_int(&params, 0x09).ok_or(status::MISSING_PARAMETER)? as u8;
+                let rp_id = map_get_text(&params, 0x0a);
+                let shared_secret = self.key_agreement.shared_secret(platform_key, protocol);
+
+                if self.force_change_pin {
+                    return Err(status::PIN_POLICY_VIOLATION);
+                }
+                let pin = self.pin.as_ref().ok_or(status::NOT_ALLOWED)?;
+                let mut hasher = Sha256::new();
+                hasher.update(pin);
+                let expected = &hasher.finalize()[..16];
+                let decrypted = shared_secret.decrypt(&pin_hash_enc);
+                if decrypted != expected {
+                    return Err(status::PIN_AUTH_INVALID);
+                }
+
+                let token_bytes = crypto::generate_token();
+                self.token = Some(IssuedToken {
+                    bytes: token_bytes,
+                    permissions,
+                    rp_id,
+                    protocol,
+                });
+                let encrypted = shared_secret.encrypt(&token_bytes);
+                Ok(MapBuilder::new().entry(0x02, Value::Bytes(encrypted)).build())
+            }
+            _ => Err(status::INVALID_COMMAND),
+        }
+    }
+
+    /// Verifies `pin_auth` over `message`, checking both the HMAC and that
+    /// the current token grants `permission` for `rp_id` (if given).
+    fn check_pin_auth(
+        &self,
+        pin_auth: Option<&[u8]>,
+        message: &[u8],
+        permission: u8,
+        rp_id: Option<&str>,
+    ) -> Result<bool, u8> {
+        let Some(pin_auth) = pin_auth else {
+            return Ok(false);
+        };
+        let token = self.token.as_ref().ok_or(status::PIN_AUTH_INVALID)?;
+        if token.authenticate(message) != pin_auth {
+            return Err(status::PIN_AUTH_INVALID);
+        }
+        if token.permissions & permission == 0 {
+            return Err(status::PIN_AUTH_INVALID);
+        }
+        if let (Some(token_rp_id), Some(rp_id)) = (&token.rp_id, rp_id) {
+            if token_rp_id != rp_id {
+                return Err(status::PIN_AUTH_INVALID);
+            }
+        }
+        Ok(true)
+    }
+
+    fn make_credential(&mut self, params: Option<Value>) -> Result<Value, u8> {
+        let params = params.ok_or(status::MISSING_PARAMETER)?;
+        let client_data_hash = map_get_bytes(&params, 0x01).ok_or(status::MISSING_PARAMETER)?;
+        let rp = map_get(&params, 0x02).ok_or(status::MISSING_PARAMETER)?;
+        let user = map_get(&params, 0x03).ok_or(status::MISSING_PARAMETER)?;
+        let pub_key_cred_params =
+            map_get(&params, 0x04).and_then(Value::as_array).ok_or(status::MISSING_PARAMETER)?;
+        let extensions = map_get(&params, 0x06);
+        let options = map_get(&params, 0x07);
+        let pin_auth = map_get_bytes(&params, 0x08);
+        let attestation_formats_preference = map_get(&params, 0x0b).and_then(Value::as_array);
+
+        let uv = self.check_pin_auth(pin_auth.as_deref(), &client_data_hash, 0x01, Some(&rp_id(&rp)))?;
+
+        let supports_es256 = pub_key_cred_params.iter().any(|param| {
+            map_get_text_field(param, "alg") == Some(-7)
+        });
+        if !supports_es256 {
+            return Err(status::UNSUPPORTED_ALGORITHM);
+        }
+
+        let rk = options
+            .and_then(|o| map_get_bool_field(o, "rk"))
+            .unwrap_or(false);
+
+        let signing_key = SigningKey::random(&mut OsRng);
+        let mut cred_id = vec![0u8; 32];
+        OsRng.fill_bytes(&mut cred_id);
+
+        let rp_id_str = rp_id(&rp);
+        let rp_id_hash: [u8; 32] = Sha256::digest(rp_id_str.as_bytes()).into();
+
+        let third_party_payment = extensions
+            .and_then(|e| map_get_bool_field(e, "thirdPartyPayment"))
+            .unwrap_or(false);
+        let min_pin_length_requested = extensions
+            .and_then(|e| map_get_bool_field(e, "minPinLength"))
+            .unwrap_or(false);
+        let min_pin_length_allowed = self.min_pin_length_rp_ids.is_empty()
+            || self.min_pin_length_rp_ids.iter().any(|allowed| allowed == &rp_id_str);
+
+        self.credentials.push(StoredCredential {
+            rp_id: rp_id_str.clone(),
+            rp_id_hash,
+            user_id: map_get_bytes_field(&user, "id").unwrap_or_default(),
+            user_name: map_get_text_field_str(&user, "name"),
+            user_display_name: map_get_text_field_str(&user, "displayName"),
+            id: cred_id.clone(),
+            signing_key: signing_key.clone(),
+            sign_count: 0,
+            resident: rk,
+            third_party_payment,
+        });
+
+        let mut flags = FLAG_UP | FLAG_AT;
+        if uv {
+            flags |= FLAG_UV;
+        }
+        let mut extension_entries = Vec::new();
+        if third_party_payment {
+            extension_entries.push((Value::Text("thirdPartyPayment".into()), Value::Bool(true)));
+        }
+        if min_pin_length_requested && min_pin_length_allowed {
+            extension_entries.push((
+                Value::Text("minPinLength".into()),
+                Value::Integer((self.min_pin_length as i128).into()),
+            ));
+        }
+        let extensions_cbor = if extension_entries.is_empty() {
+            None
+        } else {
+            flags |= FLAG_ED;
+            Some(Value::Map(extension_entries))
+        };
+
+        let auth_data = encode_auth_data(
+            rp_id_hash,
+            flags,
+            0,
+            Some((&AAGUID, &cred_id, &signing_key)),
+            extensions_cbor.as_ref(),
+        );
+
+        let requested_formats: Option<Vec<String>> = attestation_formats_preference.map(|fmts| {
+            fmts.iter().filter_map(|v| v.as_text().map(str::to_owned)).collect()
+        });
+
+        let omit_attestation = matches!(requested_formats.as_deref(), Some([only]) if only == "none");
+        let fmt = match &requested_formats {
+            Some(preferred) => preferred
+                .iter()
+                .find(|f| f.as_str() == "packed" || f.as_str() == "none")
+                .cloned()
+                .unwrap_or_else(|| "packed".to_owned()),
+            None => "packed".to_owned(),
+        };
+
+        let att_stmt = if omit_attestation {
+            None
+        } else if fmt == "packed" {
+            let sig = attestation::sign(&auth_data, &client_data_hash);
+            Some(Value::Map(vec![
+                (Value::Text("alg".into()), Value::Integer((-7).into())),
+                (Value::Text("sig".into()), Value::Bytes(sig)),
+                (
+                    Value::Text("x5c".into()),
+                    Value::Array(vec![Value::Bytes(attestation::ATTESTATION_CERT_DER.to_vec())]),
+                ),
+            ]))
+        } else {
+            Some(Value::Map(Vec::new()))
+        };
+
+        Ok(MapBuilder::new()
+            .entry(0x01, Value::Text(fmt))
+            .entry(0x02, Value::Bytes(auth_data))
+            .maybe_entry(0x03, att_stmt)
+            .build())
+    }
+
+    fn get_assertion(&mut self, params: Option<Value>) -> Result<Value, u8> {
+        let params = params.ok_or(status::MISSING_PARAMETER)?;
+        let rp_id_str = map_get_text(&params, 0x01).ok_or(status::MISSING_PARAMETER)?;
+        let client_data_hash = map_get_bytes(&params, 0x02).ok_or(status::MISSING_PARAMETER)?;
+        let allow_list = map_get(&params, 0x03).and_then(Value::as_array);
+        let extensions = map_get(&params, 0x04);
+        let pin_auth = map_get_bytes(&params, 0x06);
+
+        let uv = self.check_pin_auth(pin_auth.as_deref(), &client_data_hash, 0x02, Some(&rp_id_str))?;
+
+        let rp_id_hash: [u8; 32] = Sha256::digest(rp_id_str.as_bytes()).into();
+        let matching: Vec<usize> = self
+            .credentials
+            .iter()
+            .enumerate()
+            .filter(|(_, cred)| {
+                cred.rp_id_hash == rp_id_hash
+                    && allow_list
+                        .map(|list| {
+                            list.iter()
+                                .any(|desc| map_get_bytes_field(desc, "id").as_deref() == Some(cred.id.as_slice()))
+                        })
+                        .unwrap_or(cred.resident)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let third_party_payment = extensions
+            .and_then(|e| map_get_bool_field(e, "thirdPartyPayment"))
+            .unwrap_or(false);
+
+        let mut matching = matching.into_iter();
+        let index = matching.next().ok_or(status::NO_CREDENTIALS)?;
+        let number_of_credentials = matching.clone().count() + 1;
+        self.pending_assertions = matching
+            .map(|index| PendingAssertion {
+                index,
+                rp_id_hash,
+                client_data_hash: client_data_hash.clone(),
+                uv,
+                third_party_payment,
+            })
+            .collect();
+
+        let include_user = number_of_credentials > 1;
+        let number_of_credentials = (number_of_credentials > 1).then_some(number_of_credentials as u32);
+        self.build_assertion(
+            index,
+            rp_id_hash,
+            &client_data_hash,
+            uv,
+            third_party_payment,
+            number_of_credentials,
+            include_user,
+        )
+    }
+
+    /// `authenticatorGetNextAssertion`: pops and builds the next credential
+    /// queued by a preceding `authenticatorGetAssertion` call.
+    fn get_next_assertion(&mut self) -> Result<Value, u8> {
+        let pending = self.pending_assertions.pop_front().ok_or(status::NOT_ALLOWED)?;
+        self.build_assertion(
+            pending.index,
+            pending.rp_id_hash,
+            &pending.client_data_hash,
+            pending.uv,
+            pending.third_party_payment,
+            None,
+            true,
+        )
+    }
+
+    fn build_assertion(
+        &mut self,
+        index: usize,
+        rp_id_hash: [u8; 32],
+        client_data_hash: &[u8],
+        uv: bool,
+        third_party_payment: bool,
+        number_of_credentials: Option<u32>,
+        include_user: bool,
+    ) -> Result<Value, u8> {
+        let cred = &mut self.credentials[index];
+        cred.sign_count += 1;
+        let mut flags = FLAG_UP;
+        if uv {
+            flags |= FLAG_UV;
+        }
+        let extensions_cbor = if third_party_payment {
+            flags |= FLAG_ED;
+            Some(Value::Map(vec![(
+                Value::Text("thirdPartyPayment".into()),
+                Value::Bool(cred.third_party_payment),
+            )]))
+        } else {
+            None
+        };
+        let auth_data = encode_auth_data(rp_id_hash, flags, cred.sign_count, None, extensions_cbor.as_ref());
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(client_data_hash);
+        let signature: p256::ecdsa::Signature = p256::ecdsa::signature::Signer::sign(&cred.signing_key, &signed);
+        let user = include_user.then(|| {
+            Value::Map(vec![(Value::Text("id".into()), Value::Bytes(cred.user_id.clone()))])
+        });
+
+        Ok(MapBuilder::new()
+            .entry(
+                0x01,
+                Value::Map(vec![
+                    (Value::Text("type".into()), Value::Text("public-key".into())),
+                    (Value::Text("id".into()), Value::Bytes(cred.id.clone())),
+                ]),
+            )
+            .entry(0x02, Value::Bytes(auth_data))
+            .entry(0x03, Value::Bytes(signature.to_der().as_bytes().to_vec()))
+            .maybe_entry(0x04, user)
+            .maybe_entry(0x05, number_of_credentials.map(|n| n as i128))
+            .build())
+    }
+
+    fn credential_management(&mut self, params: Option<Value>) -> Result<Value, u8> {
+        let params = params.ok_or(status::MISSING_PARAMETER)?;
+        let subcommand = map_get_int(&params, 0x01).ok_or(status::MISSING_PARAMETER)? as u8;
+        let subcommand_params = map_get(&params, 0x02);
+        let pin_auth = map_get_bytes(&params, 0x04);
+
+        let mut message = vec![subcommand];
+        if let Some(subcommand_params) = subcommand_params {
+            let mut buf = Vec::new();
+            ciborium::ser::into_writer(subcommand_params, &mut buf).unwrap();
+            message.extend(buf);
+        }
+        self.check_pin_auth(pin_auth.as_deref(), &message, 0x04, None)?;
+
+        match subcommand {
+            0x02 => {
+                let rps: BTreeMap<[u8; 32], &str> = self
+                    .credentials
+                    .iter()
+                    .filter(|c| c.resident)
+                    .map(|c| (c.rp_id_hash, c.rp_id.as_str()))
+                    .collect();
+                let (rp_id_hash, rp_id) = rps.iter().next().ok_or(status::NO_CREDENTIALS)?;
+                Ok(MapBuilder::new()
+                    .entry(
+                        0x03,
+                        Value::Map(vec![(Value::Text("id".into()), Value::Text((*rp_id).to_owned()))]),
+                    )
+                    .entry(0x04, Value::Bytes(rp_id_hash.to_vec()))
+                    .entry(0x05, rps.len() as i128)
+                    .build())
+            }
+            0x04 => {
+                let subcommand_params = subcommand_params.ok_or(status::MISSING_PARAMETER)?;
+                let rp_id_hash =
+                    map_get_bytes(subcommand_params, 0x01).ok_or(status::MISSING_PARAMETER)?;
+                let creds: Vec<usize> = self
+                    .credentials
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| c.resident && c.rp_id_hash.as_slice() == rp_id_hash.as_slice())
+                    .map(|(i, _)| i)
+                    .collect();
+                let index = *creds.first().ok_or(status::NO_CREDENTIALS)?;
+                let cred = &self.credentials[index];
+                Ok(MapBuilder::new()
+                    .entry(
+                        0x06,
+                        Value::Map(vec![(Value::Text("id".into()), Value::Bytes(cred.user_id.clone()))]),
+                    )
+                    .entry(0x09, creds.len() as i128)
+                    .entry(0x64, cred.third_party_payment)
+                    .build())
+            }
+            _ => Err(status::INVALID_COMMAND),
+        }
+    }
+
+    /// `getFingerprintSensorInfo` needs neither authentication nor an
+    /// enrolled sensor state, so it's carved out of the `pinUvAuthParam`
+    /// check below; every other subcommand requires a token with the
+    /// `BioEnrollment` permission (0x08).
+    fn bio_enrollment(&mut self, params: Option<Value>) -> Result<Value, u8> {
+        let params = params.unwrap_or(Value::Map(Vec::new()));
+        let subcommand = map_get_int(&params, 0x02).ok_or(status::MISSING_PARAMETER)? as u8;
+
+        if subcommand == BIO_SUBCOMMAND_GET_FINGERPRINT_SENSOR_INFO {
+            return Ok(MapBuilder::new()
+                .entry(0x01, 1i128) // modality: fingerprint
+                .entry(0x02, 1i128) // fingerprintKind: touch
+                .entry(0x03, BIO_MAX_CAPTURE_SAMPLES as i128)
+                .entry(0x08, BIO_MAX_TEMPLATE_FRIENDLY_NAME as i128)
+                .build());
+        }
+
+        let subcommand_params = map_get(&params, 0x03);
+        let pin_auth = map_get_bytes(&params, 0x05);
+        let mut message = vec![subcommand];
+        if let Some(subcommand_params) = subcommand_params {
+            let mut buf = Vec::new();
+            ciborium::ser::into_writer(subcommand_params, &mut buf).unwrap();
+            message.extend(buf);
+        }
+        if !self.check_pin_auth(pin_auth.as_deref(), &message, 0x08, None)? {
+            return Err(status::MISSING_PARAMETER);
+        }
+
+        match subcommand {
+            BIO_SUBCOMMAND_ENROLL_BEGIN => {
+                let mut template_id = vec![0u8; 16];
+                OsRng.fill_bytes(&mut template_id);
+                self.bio_enrollment = Some(BioEnrollmentState {
+                    template_id: template_id.clone(),
+                    remaining_samples: BIO_MAX_CAPTURE_SAMPLES - 1,
+                });
+                Ok(MapBuilder::new()
+                    .entry(0x04, Value::Bytes(template_id))
+                    .entry(0x05, status::SUCCESS as i128)
+                    .entry(0x06, (BIO_MAX_CAPTURE_SAMPLES - 1) as i128)
+                    .build())
+            }
+            BIO_SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE => {
+                let enrollment = self.bio_enrollment.as_mut().ok_or(status::NOT_ALLOWED)?;
+                enrollment.remaining_samples = enrollment.remaining_samples.saturating_sub(1);
+                let remaining_samples = enrollment.remaining_samples;
+                let template_id = enrollment.template_id.clone();
+                if remaining_samples == 0 {
+                    self.bio_templates.push(BioTemplate {
+                        id: template_id.clone(),
+                        friendly_name: None,
+                    });
+                    self.bio_enrollment = None;
+                }
+                Ok(MapBuilder::new()
+                    .entry(0x04, Value::Bytes(template_id))
+                    .entry(0x05, status::SUCCESS as i128)
+                    .entry(0x06, remaining_samples as i128)
+                    .build())
+            }
+            BIO_SUBCOMMAND_CANCEL_CURRENT_ENROLLMENT => {
+                self.bio_enrollment = None;
+                Ok(Value::Map(Vec::new()))
+            }
+            BIO_SUBCOMMAND_ENUMERATE_ENROLLMENTS => {
+                if self.bio_templates.is_empty() {
+                    return Err(status::NO_CREDENTIALS);
+                }
+                let infos = self
+                    .bio_templates
+                    .iter()
+                    .map(|template| {
+                        let mut entries = vec![(
+                            Value::Text("templateId".into()),
+                            Value::Bytes(template.id.clone()),
+                        )];
+                        if let Some(name) = &template.friendly_name {
+                            entries.push((
+                                Value::Text("templateFriendlyName".into()),
+                                Value::Text(name.clone()),
+                            ));
+                        }
+                        Value::Map(entries)
+                    })
+                    .collect();
+                Ok(MapBuilder::new().entry(0x07, Value::Array(infos)).build())
+            }
+            BIO_SUBCOMMAND_SET_FRIENDLY_NAME => {
+                let subcommand_params = subcommand_params.ok_or(status::MISSING_PARAMETER)?;
+                let template_id =
+                    map_get_bytes(subcommand_params, 0x01).ok_or(status::MISSING_PARAMETER)?;
+                let friendly_name =
+                    map_get_text(subcommand_params, 0x02).ok_or(status::MISSING_PARAMETER)?;
+                let template = self
+                    .bio_templates
+                    .iter_mut()
+                    .find(|template| template.id == template_id)
+                    .ok_or(status::NO_CREDENTIALS)?;
+                template.friendly_name = Some(friendly_name);
+                Ok(Value::Map(Vec::new()))
+            }
+            BIO_SUBCOMMAND_REMOVE_ENROLLMENT => {
+                let subcommand_params = subcommand_params.ok_or(status::MISSING_PARAMETER)?;
+                let template_id =
+                    map_get_bytes(subcommand_params, 0x01).ok_or(status::MISSING_PARAMETER)?;
+                let index = self
+                    .bio_templates
+                    .iter()
+                    .position(|template| template.id == template_id)
+                    .ok_or(status::NO_CREDENTIALS)?;
+                self.bio_templates.remove(index);
+                Ok(Value::Map(Vec::new()))
+            }
+            _ => Err(status::INVALID_COMMAND),
+        }
+    }
+
+    /// Every subcommand is authorized via a pinUvAuthToken with the
+    /// `AuthenticatorConfiguration` permission (0x20) over
+    /// `0x0d || 0xff*32 || subCommand || subCommandParams`.
+    fn authenticator_config(&mut self, params: Option<Value>) -> Result<Value, u8> {
+        let params = params.ok_or(status::MISSING_PARAMETER)?;
+        let subcommand = map_get_int(&params, 0x01).ok_or(status::MISSING_PARAMETER)? as u8;
+        let subcommand_params = map_get(&params, 0x02);
+        let pin_auth = map_get_bytes(&params, 0x04);
+
+        let mut message = vec![0x0d];
+        message.extend_from_slice(&[0xff; 32]);
+        message.push(subcommand);
+        if let Some(subcommand_params) = subcommand_params {
+            let mut buf = Vec::new();
+            ciborium::ser::into_writer(subcommand_params, &mut buf).unwrap();
+            message.extend(buf);
+        }
+        if !self.check_pin_auth(pin_auth.as_deref(), &message, 0x20, None)? {
+            return Err(status::MISSING_PARAMETER);
+        }
+
+        match subcommand {
+            CONFIG_SUBCOMMAND_ENABLE_ENTERPRISE_ATTESTATION => {
+                self.enterprise_attestation = true;
+                Ok(Value::Map(Vec::new()))
+            }
+            CONFIG_SUBCOMMAND_TOGGLE_ALWAYS_UV => {
+                self.always_uv = !self.always_uv;
+                Ok(Value::Map(Vec::new()))
+            }
+            CONFIG_SUBCOMMAND_SET_MIN_PIN_LENGTH => {
+                let subcommand_params = subcommand_params.ok_or(status::MISSING_PARAMETER)?;
+                if let Some(new_min_pin_length) = map_get_int(subcommand_params, 0x01) {
+                    self.min_pin_length = new_min_pin_length as u8;
+                }
+                if let Some(rp_ids) = map_get(subcommand_params, 0x02).and_then(Value::as_array) {
+                    self.min_pin_length_rp_ids = rp_ids
+                        .iter()
+                        .filter_map(|v| v.as_text().map(str::to_owned))
+                        .collect();
+                }
+                if map_get(subcommand_params, 0x03).and_then(Value::as_bool).unwrap_or(false) {
+                    self.force_change_pin = true;
+                }
+                Ok(Value::Map(Vec::new()))
+            }
+            _ => Err(status::INVALID_COMMAND),
+        }
+    }
+
+    /// Offset-addressed reads and writes of the serialized
+    /// `largeBlobArray`. Reads need no authentication; writes are
+    /// authorized via a pinUvAuthToken with the `LargeBlobWrite`
+    /// permission (0x10) over `0xff*32 || 0x0c || 0x00 ||
+    /// offset(4 bytes LE) || SHA-256(fragment)`.
+    fn large_blobs(&mut self, params: Option<Value>) -> Result<Value, u8> {
+        let params = params.ok_or(status::MISSING_PARAMETER)?;
+        let get = map_get_int(&params, 0x01).map(|n| n as usize);
+        let offset = map_get_int(&params, 0x03).ok_or(status::MISSING_PARAMETER)? as usize;
+
+        if let Some(count) = get {
+            let count = count.min(LARGE_BLOB_MAX_FRAGMENT_LENGTH);
+            let fragment = match self.large_blob_array.get(offset..) {
+                Some(rest) => &rest[..count.min(rest.len())],
+                None => &[],
+            };
+            return Ok(MapBuilder::new().entry(0x01, Value::Bytes(fragment.to_vec())).build());
+        }
+
+        let fragment = map_get_bytes(&params, 0x02).ok_or(status::MISSING_PARAMETER)?;
+        let pin_auth = map_get_bytes(&params, 0x05);
+
+        let mut message = vec![0xff; 32];
+        message.push(0x0c);
+        message.push(0x00);
+        message.extend_from_slice(&(offset as u32).to_le_bytes());
+        let mut hasher = Sha256::new();
+        hasher.update(&fragment);
+        message.extend_from_slice(&hasher.finalize());
+        if !self.check_pin_auth(pin_auth.as_deref(), &message, 0x10, None)? {
+            return Err(status::MISSING_PARAMETER);
+        }
+
+        if offset == 0 {
+            let length = map_get_int(&params, 0x04).ok_or(status::MISSING_PARAMETER)? as usize;
+            if length > LARGE_BLOB_MAX_SERIALIZED_ARRAY {
+                return Err(status::LARGE_BLOB_STORAGE_FULL);
+            }
+            if length < LARGE_BLOB_MIN_SERIALIZED_ARRAY {
+                return Err(status::INVALID_LENGTH);
+            }
+            self.pending_large_blob_write = Some(PendingLargeBlobWrite {
+                expected_length: length,
+                buffer: Vec::with_capacity(length),
+            });
+        }
+
+        let pending = self
+            .pending_large_blob_write
+            .as_mut()
+            .ok_or(status::INVALID_SEQ)?;
+        if offset != pending.buffer.len() {
+            self.pending_large_blob_write = None;
+            return Err(status::INVALID_SEQ);
+        }
+        pending.buffer.extend_from_slice(&fragment);
+        if pending.buffer.len() < pending.expected_length {
+            return Ok(Value::Map(Vec::new()));
+        }
+
+        let pending = self.pending_large_blob_write.take().unwrap();
+        let (contents, checksum) = pending.buffer.split_at(pending.buffer.len() - 16);
+        let mut hasher = Sha256::new();
+        hasher.update(contents);
+        if &hasher.finalize()[..16] != checksum {
+            return Err(status::INTEGRITY_FAILURE);
+        }
+        self.large_blob_array = pending.buffer;
+        Ok(Value::Map(Vec::new()))
+    }
+}
+
+/// The serialization of an empty CBOR array (`0x80`) followed by its
+/// SHA-256 hash truncated to the first 16 bytes: the large-blob array's
+/// value right after a reset.
+fn empty_large_blob_array() -> Vec<u8> {
+    let mut array = vec![0x80];
+    let mut hasher = Sha256::new();
+    hasher.update(&array);
+    array.extend_from_slice(&hasher.finalize()[..16]);
+    array
+}
+
+const LARGE_BLOB_MAX_FRAGMENT_LENGTH: usize = 960;
+const LARGE_BLOB_MAX_SERIALIZED_ARRAY: usize = 2048;
+/// The smallest valid serialization: the empty CBOR array (`0x80`, one
+/// byte) plus its 16-byte truncated checksum.
+const LARGE_BLOB_MIN_SERIALIZED_ARRAY: usize = 17;
+
+const CONFIG_SUBCOMMAND_ENABLE_ENTERPRISE_ATTESTATION: u8 = 0x01;
+const CONFIG_SUBCOMMAND_TOGGLE_ALWAYS_UV: u8 = 0x02;
+const CONFIG_SUBCOMMAND_SET_MIN_PIN_LENGTH: u8 = 0x03;
+
+/// How many samples `enrollBegin`/`enrollCaptureNextSample` simulate before
+/// a template is considered complete.
+const BIO_MAX_CAPTURE_SAMPLES: u8 = 3;
+const BIO_MAX_TEMPLATE_FRIENDLY_NAME: u8 = 32;
+
+const BIO_SUBCOMMAND_ENROLL_BEGIN: u8 = 0x01;
+const BIO_SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE: u8 = 0x02;
+const BIO_SUBCOMMAND_CANCEL_CURRENT_ENROLLMENT: u8 = 0x03;
+const BIO_SUBCOMMAND_ENUMERATE_ENROLLMENTS: u8 = 0x04;
+const BIO_SUBCOMMAND_SET_FRIENDLY_NAME: u8 = 0x05;
+const BIO_SUBCOMMAND_REMOVE_ENROLLMENT: u8 = 0x06;
+const BIO_SUBCOMMAND_GET_FINGERPRINT_SENSOR_INFO: u8 = 0x07;
+
+fn rp_id(rp: &Value) -> String {
+    map_get_text_field_str(rp, "id").unwrap_or_default()
+}
+
+fn encode_auth_data(
+    rp_id_hash: [u8; 32],
+    flags: u8,
+    sign_count: u32,
+    attested: Option<(&[u8; 16], &[u8], &SigningKey)>,
+    extensions: Option<&Value>,
+) -> Vec<u8> {
+    let mut out = rp_id_hash.to_vec();
+    out.push(flags);
+    out.extend_from_slice(&sign_count.to_be_bytes());
+    if let Some((aaguid, cred_id, signing_key)) = attested {
+        out.extend_from_slice(aaguid);
+        out.extend_from_slice(&(cred_id.len() as u16).to_be_bytes());
+        out.extend_from_slice(cred_id);
+        let point = signing_key.verifying_key().to_encoded_point(false);
+        let cose_key = Value::Map(vec![
+            (Value::Integer(1.into()), Value::Integer(2.into())),
+            (Value::Integer(3.into()), Value::Integer((-7).into())),
+            (Value::Integer((-1).into()), Value::Integer(1.into())),
+            (Value::Integer((-2).into()), Value::Bytes(point.x().unwrap().to_vec())),
+            (Value::Integer((-3).into()), Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+        ciborium::ser::into_writer(&cose_key, &mut out).unwrap();
+    }
+    if let Some(extensions) = extensions {
+        ciborium::ser::into_writer(extensions, &mut out).unwrap();
+    }
+    out
+}
+
+// --- small CBOR-map reading helpers, mirroring webauthn::cbor_util but kept
+// local so the CTAP2 handlers don't reuse the platform client's CBOR code.
+
+pub(crate) struct MapBuilder(Vec<(Value, Value)>);
+
+impl MapBuilder {
+    pub(crate) fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub(crate) fn entry(mut self, key: i128, value: impl Into<Value>) -> Self {
+        self.0.push((Value::Integer(key.into()), value.into()));
+        self
+    }
+
+    pub(crate) fn maybe_entry(self, key: i128, value: Option<impl Into<Value>>) -> Self {
+        match value {
+            Some(value) => self.entry(key, value),
+            None => self,
+        }
+    }
+
+    pub(crate) fn build(self) -> Value {
+        Value::Map(self.0)
+    }
+}
+
+fn map_get(value: &Value, key: i128) -> Option<&Value> {
+    let Value::Map(entries) = value else { return None };
+    entries.iter().find_map(|(k, v)| (k == &Value::Integer(key.into())).then_some(v))
+}
+
+fn map_get_bytes(value: &Value, key: i128) -> Option<Vec<u8>> {
+    map_get(value, key).and_then(Value::as_bytes).map(<[u8]>::to_vec)
+}
+
+fn map_get_text(value: &Value, key: i128) -> Option<String> {
+    map_get(value, key).and_then(Value::as_text).map(str::to_owned)
+}
+
+fn map_get_int(value: &Value, key: i128) -> Option<i128> {
+    map_get(value, key).and_then(Value::as_integer).map(|n| n.try_into().unwrap())
+}
+
+fn map_get_text_field(value: &Value, field: &str) -> Option<i128> {
+    map_get_text_field_value(value, field).and_then(Value::as_integer).map(|n| n.try_into().unwrap())
+}
+
+fn map_get_text_field_str(value: &Value, field: &str) -> Option<String> {
+    map_get_text_field_value(value, field).and_then(Value::as_text).map(str::to_owned)
+}
+
+fn map_get_bytes_field(value: &Value, field: &str) -> Option<Vec<u8>> {
+    map_get_text_field_value(value, field).and_then(Value::as_bytes).map(<[u8]>::to_vec)
+}
+
+fn map_get_bool_field(value: &Value, field: &str) -> Option<bool> {
+    map_get_text_field_value(value, field).and_then(Value::as_bool)
+}
+
+fn map_get_text_field_value<'a>(value: &'a Value, field: &str) -> Option<&'a Value> {
+    let Value::Map(entries) = value else { return None };
+    entries.iter().find_map(|(k, v)| (k.as_text() == Some(field)).then_some(v))
+}
@@ -0,0 +1,184 @@
+//! The authenticator side of the `pinUvAuthProtocol` one and two
+//! key-agreement schemes. Mirrors `webauthn::crypto`, but derived independently since the
+//! authenticator and the platform never share code in the real protocol.
+
+use ciborium::Value;
+use hmac::{Hmac, Mac};
+use p256::ecdh::diffie_hellman;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{EncodedPoint, PublicKey as P256PublicKey, SecretKey};
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub(crate) struct KeyAgreementKeyPair {
+    secret: SecretKey,
+}
+
+impl KeyAgreementKeyPair {
+    pub(crate) fn generate() -> Self {
+        Self {
+            secret: SecretKey::random(&mut OsRng),
+        }
+    }
+
+    pub(crate) fn public_key_cbor(&self) -> Value {
+        let point = self.secret.public_key().to_encoded_point(false);
+        let x = point.x().unwrap().to_vec();
+        let y = point.y().unwrap().to_vec();
+        Value::Map(vec![
+            (Value::Integer(1.into()), Value::Integer(2.into())),
+            (Value::Integer((-1).into()), Value::Integer((-1).into())),
+            (Value::Integer((-2).into()), Value::Bytes(x)),
+            (Value::Integer((-3).into()), Value::Bytes(y)),
+        ])
+    }
+
+    /// Derives the `pinUvAuthProtocol` shared secret from an ECDH handshake
+    /// with the platform's key-agreement key. `protocol` selects protocol
+    /// one (`SHA-256(Z)`, used as both the AES and HMAC key) or protocol two
+    /// (two independent keys via HKDF-SHA256).
+    pub(crate) fn shared_secret(&self, platform_key: &Value, protocol: u8) -> SharedSecret {
+        let x = cbor_bytes(platform_key, -2);
+        let y = cbor_bytes(platform_key, -3);
+        let point = EncodedPoint::from_affine_coordinates(x.as_slice().into(), y.as_slice().into(), false);
+        let platform_public = P256PublicKey::from_sec1_bytes(point.as_bytes()).unwrap();
+        let shared = diffie_hellman(self.secret.to_nonzero_scalar(), platform_public.as_affine());
+        let z = shared.raw_secret_bytes();
+
+        if protocol == 1 {
+            let key: [u8; 32] = Sha256::digest(z.as_slice()).into();
+            return SharedSecret {
+                hmac_key: key,
+                aes_key: key,
+                protocol,
+            };
+        }
+
+        let hk = hkdf::Hkdf::<Sha256>::new(Some(&[0u8; 32]), z.as_slice());
+        let mut hmac_key = [0u8; 32];
+        let mut aes_key = [0u8; 32];
+        hk.expand(b"CTAP2 HMAC key", &mut hmac_key).unwrap();
+        hk.expand(b"CTAP2 AES key", &mut aes_key).unwrap();
+        SharedSecret { hmac_key, aes_key, protocol }
+    }
+}
+
+fn cbor_bytes(value: &Value, key: i128) -> Vec<u8> {
+    let Value::Map(entries) = value else {
+        panic!("COSE key must be a CBOR map");
+    };
+    entries
+        .iter()
+        .find_map(|(k, v)| {
+            if k == &Value::Integer(key.into()) {
+                match v {
+                    Value::Bytes(bytes) => Some(bytes.clone()),
+                    _ => None,
+                }
+            } else {
+                None
+            }
+        })
+        .expect("COSE key missing coordinate")
+}
+
+pub(crate) struct SharedSecret {
+    hmac_key: [u8; 32],
+    aes_key: [u8; 32],
+    protocol: u8,
+}
+
+impl SharedSecret {
+    pub(crate) fn decrypt(&self, data: &[u8]) -> Vec<u8> {
+        if self.protocol == 1 {
+            return cbc_decrypt(&self.aes_key, &[0u8; 16], data);
+        }
+        let (iv, ciphertext) = data.split_at(16);
+        cbc_decrypt(&self.aes_key, iv, ciphertext)
+    }
+
+    pub(crate) fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        use rand_core::RngCore;
+        if self.protocol == 1 {
+            return cbc_encrypt(&self.aes_key, &[0u8; 16], plaintext);
+        }
+        let mut iv = [0u8; 16];
+        OsRng.fill_bytes(&mut iv);
+        let mut out = iv.to_vec();
+        out.extend(cbc_encrypt(&self.aes_key, &iv, plaintext));
+        out
+    }
+
+    /// Verifies a `pinUvAuthParam`: the full 32-byte HMAC tag for protocol
+    /// two, or its first 16 bytes for protocol one.
+    pub(crate) fn verify(&self, message: &[u8], tag: &[u8]) -> bool {
+        let mut mac = HmacSha256::new_from_slice(&self.hmac_key).unwrap();
+        mac.update(message);
+        if self.protocol == 1 {
+            mac.verify_truncated_left(tag).is_ok()
+        } else {
+            mac.verify_slice(tag).is_ok()
+        }
+    }
+}
+
+fn cbc_encrypt(key: &[u8; 32], iv: &[u8; 16], plaintext: &[u8]) -> Vec<u8> {
+    use aes::cipher::{BlockEncrypt, KeyInit};
+    let cipher = aes::Aes256::new(key.into());
+    let mut prev = *iv;
+    let mut out = Vec::with_capacity(plaintext.len());
+    for chunk in plaintext.chunks(16) {
+        let mut block = [0u8; 16];
+        block.copy_from_slice(chunk);
+        for i in 0..16 {
+            block[i] ^= prev[i];
+        }
+        cipher.encrypt_block((&mut block).into());
+        out.extend_from_slice(&block);
+        prev = block;
+    }
+    out
+}
+
+fn cbc_decrypt(key: &[u8; 32], iv: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    use aes::cipher::{BlockDecrypt, KeyInit};
+    let cipher = aes::Aes256::new(key.into());
+    let mut prev = [0u8; 16];
+    prev.copy_from_slice(iv);
+    let mut out = Vec::with_capacity(ciphertext.len());
+    for chunk in ciphertext.chunks(16) {
+        let mut block = [0u8; 16];
+        block.copy_from_slice(chunk);
+        let ciphertext_block = block;
+        cipher.decrypt_block((&mut block).into());
+        for i in 0..16 {
+            block[i] ^= prev[i];
+        }
+        out.extend_from_slice(&block);
+        prev = ciphertext_block;
+    }
+    out
+}
+
+/// Generate a fresh 32-byte `pinUvAuthToken`.
+pub(crate) fn generate_token() -> [u8; 32] {
+    use rand_core::RngCore;
+    let mut token = [0u8; 32];
+    OsRng.fill_bytes(&mut token);
+    token
+}
+
+/// `HMAC-SHA256(key, message)`, truncated to 16 bytes for `pinUvAuthProtocol`
+/// one or kept at the full 32 bytes for protocol two.
+pub(crate) fn hmac_authenticate(key: &[u8; 32], message: &[u8], protocol: u8) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).unwrap();
+    mac.update(message);
+    let tag = mac.finalize().into_bytes().to_vec();
+    if protocol == 1 {
+        tag[..16].to_vec()
+    } else {
+        tag
+    }
+}
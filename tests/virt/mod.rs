@@ -0,0 +1,135 @@
+//! An in-process virtual authenticator: a [`Authenticator`] behind CTAP2,
+//! CTAP1/U2F and CTAP-HID handles, so the test client can run without a
+//! physical device attached. CTAP2 requests are handed over as decoded CBOR
+//! values and U2F requests as command bodies; no CTAP-HID framing or APDU
+//! header encoding takes place.
+
+mod attestation;
+mod authenticator;
+mod crypto;
+mod status;
+mod u2f;
+
+use std::cell::RefCell;
+
+use authenticator::Authenticator;
+use crate::webauthn::u2f::{RegisterRequest, RegisterResponse, AuthenticateRequest, AuthenticateResponse};
+use crate::webauthn::{Ctap2Reply, Ctap2Request};
+
+/// A CTAP2 error, carrying the raw status byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ctap2Error(pub u8);
+
+/// A CTAP1/U2F error, carrying the raw status word (`SW1 SW2`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ctap1Error(pub u16);
+
+/// A handle to the virtual authenticator's CTAP2 interface.
+pub struct Ctap2 {
+    state: RefCell<Authenticator>,
+}
+
+impl Ctap2 {
+    fn new() -> Self {
+        Self {
+            state: RefCell::new(Authenticator::new()),
+        }
+    }
+
+    /// Dispatches `request`'s `CMD` and `params` CBOR value to the virtual
+    /// authenticator and parses the reply value. The params are passed as a
+    /// `Value`; they are not serialized to bytes.
+    pub fn exec<R: Ctap2Request>(&self, request: R) -> Result<R::Reply, Ctap2Error> {
+        let params = request.params();
+        match self.state.borrow_mut().handle_cbor(R::CMD, params) {
+            Ok(value) => Ok(R::Reply::parse(value)),
+            Err(status) => Err(Ctap2Error(status)),
+        }
+    }
+
+    /// Borrows this device's CTAP1/U2F interface, so credentials minted
+    /// over one protocol can be asserted over the other.
+    pub fn as_ctap1(&self) -> Ctap1<'_> {
+        Ctap1 { state: &self.state }
+    }
+
+    /// Simulates the user granting or withholding presence (a touch) for
+    /// presence-requiring commands, e.g. `authenticatorReset`, until it is
+    /// called again.
+    pub fn set_user_presence(&self, granted: bool) {
+        self.state.borrow_mut().user_presence_granted = granted;
+    }
+}
+
+/// Spins up a fresh virtual authenticator and runs `f` against its CTAP2
+/// interface.
+pub fn run_ctap2<F: FnOnce(&Ctap2)>(f: F) {
+    let device = Ctap2::new();
+    f(&device);
+}
+
+/// A handle to the virtual authenticator's CTAP1/U2F interface.
+pub struct Ctap1<'a> {
+    state: &'a RefCell<Authenticator>,
+}
+
+impl Ctap1<'_> {
+    pub fn register(&self, request: RegisterRequest) -> Result<RegisterResponse, Ctap1Error> {
+        let (data, status) = self
+            .state
+            .borrow_mut()
+            .handle_ctap1(crate::webauthn::u2f::INS_REGISTER, 0, &request.encode());
+        if status == crate::webauthn::u2f::SW_NO_ERROR {
+            Ok(RegisterResponse::decode(&data))
+        } else {
+            Err(Ctap1Error(status))
+        }
+    }
+
+    pub fn authenticate(&self, request: AuthenticateRequest) -> Result<AuthenticateResponse, Ctap1Error> {
+        let (data, status) = self.state.borrow_mut().handle_ctap1(
+            crate::webauthn::u2f::INS_AUTHENTICATE,
+            request.control,
+            &request.encode(),
+        );
+        if status == crate::webauthn::u2f::SW_NO_ERROR {
+            Ok(AuthenticateResponse::decode(&data))
+        } else {
+            Err(Ctap1Error(status))
+        }
+    }
+
+    pub fn version(&self) -> Result<String, Ctap1Error> {
+        let (data, status) = self
+            .state
+            .borrow_mut()
+            .handle_ctap1(crate::webauthn::u2f::INS_VERSION, 0, &[]);
+        if status == crate::webauthn::u2f::SW_NO_ERROR {
+            Ok(String::from_utf8(data).unwrap())
+        } else {
+            Err(Ctap1Error(status))
+        }
+    }
+}
+
+/// Spins up a fresh virtual authenticator and runs `f` against its CTAP1
+/// interface.
+pub fn run_ctap1<F: FnOnce(&Ctap1)>(f: F) {
+    let device = Ctap2::new();
+    f(&device.as_ctap1());
+}
+
+/// A handle to the virtual authenticator's CTAP-HID interface (just `ping`
+/// for now; CBOR commands go through [`Ctap2`]/[`run_ctap2`] instead).
+pub struct CtapHid;
+
+impl CtapHid {
+    /// Echoes `payload` back, as CTAPHID_PING does over the real transport.
+    pub fn ping(&self, payload: &[u8]) -> Result<Vec<u8>, Ctap2Error> {
+        Ok(payload.to_vec())
+    }
+}
+
+pub fn run_ctaphid<F: FnOnce(&CtapHid)>(f: F) {
+    f(&CtapHid);
+}
@@ -0,0 +1,19 @@
+//! CTAP1/CTAP2 status byte constants used by the virtual authenticator.
+//! Names and values follow the CTAP2.1 specification's status code table.
+
+pub(crate) const SUCCESS: u8 = 0x00;
+pub(crate) const INVALID_COMMAND: u8 = 0x01;
+pub(crate) const INVALID_LENGTH: u8 = 0x03;
+pub(crate) const MISSING_PARAMETER: u8 = 0x14;
+pub(crate) const CREDENTIAL_EXCLUDED: u8 = 0x19;
+pub(crate) const INVALID_SEQ: u8 = 0x24;
+pub(crate) const UNSUPPORTED_ALGORITHM: u8 = 0x26;
+pub(crate) const OPERATION_DENIED: u8 = 0x27;
+pub(crate) const KEEPALIVE_CANCEL: u8 = 0x2d;
+pub(crate) const NO_CREDENTIALS: u8 = 0x2e;
+pub(crate) const USER_ACTION_TIMEOUT: u8 = 0x2f;
+pub(crate) const NOT_ALLOWED: u8 = 0x30;
+pub(crate) const PIN_AUTH_INVALID: u8 = 0x33;
+pub(crate) const PIN_POLICY_VIOLATION: u8 = 0x37;
+pub(crate) const LARGE_BLOB_STORAGE_FULL: u8 = 0x39;
+pub(crate) const INTEGRITY_FAILURE: u8 = 0x3c;
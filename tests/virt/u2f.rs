@@ -0,0 +1,117 @@
+//! CTAP1/U2F command handling, layered onto the same credential store the
+//! CTAP2 handlers in [`super::authenticator`] use — a credential minted via
+//! `authenticatorMakeCredential` can be asserted via `U2F_AUTHENTICATE` as
+//! long as the platform already knows its key handle (= credential ID).
+
+use p256::ecdsa::SigningKey;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rand_core::{OsRng, RngCore};
+
+use super::attestation;
+use super::authenticator::{Authenticator, StoredCredential};
+use crate::webauthn::u2f::{
+    CONTROL_CHECK_ONLY, INS_AUTHENTICATE, INS_REGISTER, INS_VERSION, SW_CONDITIONS_NOT_SATISFIED,
+    SW_NO_ERROR, SW_WRONG_DATA,
+};
+
+/// `INS not supported`.
+const SW_INS_NOT_SUPPORTED: u16 = 0x6d00;
+/// `Wrong length`.
+const SW_WRONG_LENGTH: u16 = 0x6700;
+
+impl Authenticator {
+    /// Dispatches a raw U2F command body (everything after `CLA INS P1 P2
+    /// Lc`) and returns `(responseBody, statusWord)`.
+    pub(crate) fn handle_ctap1(&mut self, ins: u8, p1: u8, data: &[u8]) -> (Vec<u8>, u16) {
+        match ins {
+            INS_REGISTER => self.u2f_register(data),
+            INS_AUTHENTICATE => self.u2f_authenticate(p1, data),
+            INS_VERSION => (b"U2F_V2".to_vec(), SW_NO_ERROR),
+            _ => (Vec::new(), SW_INS_NOT_SUPPORTED),
+        }
+    }
+
+    fn u2f_register(&mut self, data: &[u8]) -> (Vec<u8>, u16) {
+        if data.len() != 64 {
+            return (Vec::new(), SW_WRONG_LENGTH);
+        }
+        let challenge: [u8; 32] = data[..32].try_into().unwrap();
+        let application: [u8; 32] = data[32..].try_into().unwrap();
+
+        let signing_key = SigningKey::random(&mut OsRng);
+        let mut key_handle = vec![0u8; 64];
+        OsRng.fill_bytes(&mut key_handle);
+
+        let point = signing_key.verifying_key().to_encoded_point(false);
+        let mut public_key = [0u8; 65];
+        public_key.copy_from_slice(point.as_bytes());
+
+        let mut signed = vec![0x00];
+        signed.extend_from_slice(&application);
+        signed.extend_from_slice(&challenge);
+        signed.extend_from_slice(&key_handle);
+        signed.extend_from_slice(&public_key);
+        let signature = attestation::sign_raw(&signed);
+
+        self.credentials.push(StoredCredential {
+            rp_id: String::new(),
+            rp_id_hash: application,
+            user_id: Vec::new(),
+            user_name: None,
+            user_display_name: None,
+            id: key_handle.clone(),
+            signing_key,
+            sign_count: 0,
+            resident: false,
+            third_party_payment: false,
+        });
+
+        let mut response = vec![0x05];
+        response.extend_from_slice(&public_key);
+        response.push(key_handle.len() as u8);
+        response.extend_from_slice(&key_handle);
+        response.extend_from_slice(attestation::ATTESTATION_CERT_DER);
+        response.extend_from_slice(&signature);
+        (response, SW_NO_ERROR)
+    }
+
+    fn u2f_authenticate(&mut self, control: u8, data: &[u8]) -> (Vec<u8>, u16) {
+        if data.len() < 65 {
+            return (Vec::new(), SW_WRONG_LENGTH);
+        }
+        let challenge: [u8; 32] = data[..32].try_into().unwrap();
+        let application: [u8; 32] = data[32..64].try_into().unwrap();
+        let key_handle_len = data[64] as usize;
+        if data.len() < 65 + key_handle_len {
+            return (Vec::new(), SW_WRONG_LENGTH);
+        }
+        let key_handle = &data[65..65 + key_handle_len];
+
+        let Some(index) = self
+            .credentials
+            .iter()
+            .position(|cred| cred.rp_id_hash == application && cred.id == key_handle)
+        else {
+            return (Vec::new(), SW_WRONG_DATA);
+        };
+
+        if control == CONTROL_CHECK_ONLY {
+            return (Vec::new(), SW_CONDITIONS_NOT_SATISFIED);
+        }
+
+        let cred = &mut self.credentials[index];
+        cred.sign_count += 1;
+        let user_presence = 0x01;
+
+        let mut signed = application.to_vec();
+        signed.push(user_presence);
+        signed.extend_from_slice(&cred.sign_count.to_be_bytes());
+        signed.extend_from_slice(&challenge);
+        let signature: p256::ecdsa::Signature = p256::ecdsa::signature::Signer::sign(&cred.signing_key, &signed);
+
+        let mut response = vec![user_presence];
+        response.extend_from_slice(&cred.sign_count.to_be_bytes());
+        response.extend_from_slice(signature.to_der().as_bytes());
+        (response, SW_NO_ERROR)
+    }
+}
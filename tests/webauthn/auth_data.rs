@@ -0,0 +1,107 @@
+//! Parsing of the `authData` byte string shared by `MakeCredential` and
+//! `GetAssertion` responses (CTAP2 §6.1).
+
+use std::collections::BTreeMap;
+
+use ciborium::Value;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::EncodedPoint;
+
+use super::cbor_util::ValueExt;
+
+pub(crate) const FLAG_UP: u8 = 0b0000_0001;
+pub(crate) const FLAG_UV: u8 = 0b0000_0100;
+pub(crate) const FLAG_AT: u8 = 0b0100_0000;
+pub(crate) const FLAG_ED: u8 = 0b1000_0000;
+
+#[derive(Debug)]
+pub struct AuthData {
+    pub rp_id_hash: [u8; 32],
+    pub flags: u8,
+    pub sign_count: u32,
+    pub credential: Option<Credential>,
+    pub extensions: Option<BTreeMap<String, Value>>,
+    pub(crate) raw: Vec<u8>,
+}
+
+impl AuthData {
+    pub(crate) fn parse(bytes: &[u8]) -> Self {
+        let mut rp_id_hash = [0u8; 32];
+        rp_id_hash.copy_from_slice(&bytes[..32]);
+        let flags = bytes[32];
+        let sign_count = u32::from_be_bytes(bytes[33..37].try_into().unwrap());
+
+        let mut offset = 37;
+        let credential = if flags & FLAG_AT != 0 {
+            let aaguid = bytes[offset..offset + 16].to_vec();
+            offset += 16;
+            let cred_id_len = u16::from_be_bytes(bytes[offset..offset + 2].try_into().unwrap());
+            offset += 2;
+            let id = bytes[offset..offset + cred_id_len as usize].to_vec();
+            offset += cred_id_len as usize;
+
+            let mut cursor = std::io::Cursor::new(&bytes[offset..]);
+            let cose_key: Value = ciborium::de::from_reader(&mut cursor).unwrap();
+            offset += cursor.position() as usize;
+
+            let x = cose_key.map_get(-2).and_then(ValueExt::as_bytes).unwrap();
+            let y = cose_key.map_get(-3).and_then(ValueExt::as_bytes).unwrap();
+            let point = EncodedPoint::from_affine_coordinates(x.into(), y.into(), false);
+            let public_key = VerifyingKey::from_encoded_point(&point).unwrap();
+
+            Some(Credential {
+                aaguid,
+                id,
+                public_key,
+            })
+        } else {
+            None
+        };
+
+        let extensions = if flags & FLAG_ED != 0 {
+            let mut cursor = std::io::Cursor::new(&bytes[offset..]);
+            let value: Value = ciborium::de::from_reader(&mut cursor).unwrap();
+            let Value::Map(entries) = value else {
+                panic!("extensions must be a CBOR map");
+            };
+            Some(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k.as_text_owned().unwrap(), v))
+                    .collect(),
+            )
+        } else {
+            None
+        };
+
+        Self {
+            rp_id_hash,
+            flags,
+            sign_count,
+            credential,
+            extensions,
+            raw: bytes.to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Credential {
+    pub aaguid: Vec<u8>,
+    pub id: Vec<u8>,
+    pub(crate) public_key: VerifyingKey,
+}
+
+impl Credential {
+    /// Verifies an assertion signature produced over this credential, as
+    /// returned from `GetAssertion`/`GetNextAssertion`.
+    pub fn verify_assertion(&self, auth_data: &AuthData, client_data_hash: &[u8], signature: &[u8]) {
+        let mut signed = auth_data.raw.clone();
+        signed.extend_from_slice(client_data_hash);
+        let signature = Signature::from_der(signature).unwrap();
+        self.public_key
+            .verify(&signed, &signature)
+            .expect("assertion signature must verify");
+    }
+}
@@ -0,0 +1,96 @@
+//! `authenticatorConfig` (0x0D): `enableEnterpriseAttestation`,
+//! `toggleAlwaysUv` and `setMinPINLength`.
+
+use ciborium::Value;
+
+use super::cbor_util::MapBuilder;
+use super::Ctap2Request;
+
+pub const SUBCOMMAND_ENABLE_ENTERPRISE_ATTESTATION: u8 = 0x01;
+pub const SUBCOMMAND_TOGGLE_ALWAYS_UV: u8 = 0x02;
+pub const SUBCOMMAND_SET_MIN_PIN_LENGTH: u8 = 0x03;
+
+#[derive(Clone, Debug, Default)]
+pub struct AuthenticatorConfigParams {
+    pub new_min_pin_length: Option<u8>,
+    pub min_pin_length_rp_ids: Option<Vec<String>>,
+    pub force_change_pin: Option<bool>,
+}
+
+impl AuthenticatorConfigParams {
+    pub(crate) fn to_cbor(&self) -> Value {
+        let min_pin_length_rp_ids = self.min_pin_length_rp_ids.clone().map(|rp_ids| {
+            Value::Array(rp_ids.into_iter().map(Value::Text).collect())
+        });
+        MapBuilder::new()
+            .maybe_entry(0x01, self.new_min_pin_length.map(|n| n as i128))
+            .maybe_entry(0x02, min_pin_length_rp_ids)
+            .maybe_entry(0x03, self.force_change_pin)
+            .build()
+    }
+
+    /// The CBOR-encoded `subCommandParams`, as covered by `pinUvAuthParam`.
+    pub fn serialized(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        ciborium::ser::into_writer(&self.to_cbor(), &mut buf).unwrap();
+        buf
+    }
+}
+
+pub struct AuthenticatorConfig {
+    pub subcommand: u8,
+    pub subcommand_params: Option<AuthenticatorConfigParams>,
+    pub pin_protocol: Option<u8>,
+    pub pin_auth: Option<Vec<u8>>,
+}
+
+impl AuthenticatorConfig {
+    pub fn new(subcommand: u8) -> Self {
+        Self {
+            subcommand,
+            subcommand_params: None,
+            pin_protocol: None,
+            pin_auth: None,
+        }
+    }
+
+    /// The message covered by `pinUvAuthParam`:
+    /// `0x0d || 0xff*32 || subCommand || CBOR(subCommandParams)`.
+    pub fn auth_message(subcommand: u8, subcommand_params: Option<&AuthenticatorConfigParams>) -> Vec<u8> {
+        let mut message = vec![0x0d];
+        message.extend_from_slice(&[0xff; 32]);
+        message.push(subcommand);
+        if let Some(subcommand_params) = subcommand_params {
+            message.extend(subcommand_params.serialized());
+        }
+        message
+    }
+}
+
+impl Ctap2Request for AuthenticatorConfig {
+    type Reply = AuthenticatorConfigReply;
+    const CMD: u8 = 0x0d;
+
+    fn params(&self) -> Option<Value> {
+        MapBuilder::new()
+            .entry(0x01, self.subcommand as i128)
+            .maybe_entry(
+                0x02,
+                self.subcommand_params.as_ref().map(AuthenticatorConfigParams::to_cbor),
+            )
+            .maybe_entry(0x03, self.pin_protocol.map(|p| p as i128))
+            .maybe_entry(0x04, self.pin_auth.clone())
+            .into_params()
+    }
+}
+
+/// `authenticatorConfig` has no response payload; success is the absence of
+/// an error status.
+#[derive(Debug, Default)]
+pub struct AuthenticatorConfigReply;
+
+impl AuthenticatorConfigReply {
+    pub(crate) fn parse(_value: Option<Value>) -> Self {
+        Self
+    }
+}
@@ -0,0 +1,162 @@
+//! `authenticatorBioEnrollment` (0x09). Covers the subset of subcommands
+//! needed to enroll, name, enumerate and remove fingerprint templates:
+//! `getFingerprintSensorInfo`, `enrollBegin`, `enrollCaptureNextSample`,
+//! `cancelCurrentEnrollment`, `enumerateEnrollments`, `setFriendlyName` and
+//! `removeEnrollment`.
+
+use ciborium::Value;
+
+use super::cbor_util::{MapBuilder, ValueExt};
+use super::Ctap2Request;
+
+pub const SUBCOMMAND_ENROLL_BEGIN: u8 = 0x01;
+pub const SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE: u8 = 0x02;
+pub const SUBCOMMAND_CANCEL_CURRENT_ENROLLMENT: u8 = 0x03;
+pub const SUBCOMMAND_ENUMERATE_ENROLLMENTS: u8 = 0x04;
+pub const SUBCOMMAND_SET_FRIENDLY_NAME: u8 = 0x05;
+pub const SUBCOMMAND_REMOVE_ENROLLMENT: u8 = 0x06;
+pub const SUBCOMMAND_GET_FINGERPRINT_SENSOR_INFO: u8 = 0x07;
+
+/// `0x00`, the only fingerprint enroll-sample status this fork simulates:
+/// "good capture".
+pub const ENROLL_SAMPLE_STATUS_GOOD: u8 = 0x00;
+
+#[derive(Clone, Debug, Default)]
+pub struct BioEnrollmentParams {
+    pub template_id: Option<Vec<u8>>,
+    pub template_friendly_name: Option<String>,
+}
+
+impl BioEnrollmentParams {
+    pub(crate) fn to_cbor(&self) -> Value {
+        MapBuilder::new()
+            .maybe_entry(0x01, self.template_id.clone())
+            .maybe_entry(0x02, self.template_friendly_name.clone())
+            .build()
+    }
+
+    /// The CBOR-encoded `subCommandParams`, as covered by `pinUvAuthParam`.
+    pub fn serialized(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        ciborium::ser::into_writer(&self.to_cbor(), &mut buf).unwrap();
+        buf
+    }
+}
+
+pub struct BioEnrollment {
+    pub modality: Option<u8>,
+    pub subcommand: Option<u8>,
+    pub subcommand_params: Option<BioEnrollmentParams>,
+    pub pin_protocol: Option<u8>,
+    pub pin_auth: Option<Vec<u8>>,
+}
+
+impl BioEnrollment {
+    pub fn new(subcommand: u8) -> Self {
+        Self {
+            modality: None,
+            subcommand: Some(subcommand),
+            subcommand_params: None,
+            pin_protocol: None,
+            pin_auth: None,
+        }
+    }
+
+    /// A bare `getFingerprintSensorInfo` query: the fingerprint modality
+    /// (0x01) and the subcommand, with no subcommand params or
+    /// authentication.
+    pub fn get_fingerprint_sensor_info() -> Self {
+        Self {
+            modality: Some(0x01),
+            subcommand: Some(SUBCOMMAND_GET_FINGERPRINT_SENSOR_INFO),
+            subcommand_params: None,
+            pin_protocol: None,
+            pin_auth: None,
+        }
+    }
+}
+
+impl Ctap2Request for BioEnrollment {
+    type Reply = BioEnrollmentReply;
+    const CMD: u8 = 0x09;
+
+    fn params(&self) -> Option<Value> {
+        MapBuilder::new()
+            .maybe_entry(0x01, self.modality.map(|m| m as i128))
+            .maybe_entry(0x02, self.subcommand.map(|s| s as i128))
+            .maybe_entry(
+                0x03,
+                self.subcommand_params.as_ref().map(BioEnrollmentParams::to_cbor),
+            )
+            .maybe_entry(0x04, self.pin_protocol.map(|p| p as i128))
+            .maybe_entry(0x05, self.pin_auth.clone())
+            .into_params()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BioEnrollmentReply {
+    pub modality: Option<u32>,
+    pub fingerprint_kind: Option<u32>,
+    pub max_capture_samples_required_for_enroll: Option<u32>,
+    pub template_id: Option<Vec<u8>>,
+    pub last_enroll_sample_status: Option<u32>,
+    pub remaining_samples: Option<u32>,
+    pub template_infos: Option<Vec<(Vec<u8>, Option<String>)>>,
+    pub max_template_friendly_name: Option<u32>,
+}
+
+impl BioEnrollmentReply {
+    pub(crate) fn parse(value: Option<Value>) -> Self {
+        let Some(value) = value else {
+            return Self::default();
+        };
+        let template_infos = value.map_get(0x07).and_then(Value::as_array).map(|infos| {
+            infos
+                .iter()
+                .map(|info| {
+                    let id = info
+                        .map_get_text("templateId")
+                        .and_then(ValueExt::as_bytes)
+                        .unwrap()
+                        .to_vec();
+                    let name = info
+                        .map_get_text("templateFriendlyName")
+                        .and_then(ValueExt::as_text_owned);
+                    (id, name)
+                })
+                .collect()
+        });
+        Self {
+            modality: value
+                .map_get(0x01)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+            fingerprint_kind: value
+                .map_get(0x02)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+            max_capture_samples_required_for_enroll: value
+                .map_get(0x03)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+            template_id: value
+                .map_get(0x04)
+                .and_then(ValueExt::as_bytes)
+                .map(<[u8]>::to_vec),
+            last_enroll_sample_status: value
+                .map_get(0x05)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+            remaining_samples: value
+                .map_get(0x06)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+            template_infos,
+            max_template_friendly_name: value
+                .map_get(0x08)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Small helpers for building and reading the integer-keyed CBOR maps that
+//! the CTAP2 wire format uses for request parameters and responses.
+
+use ciborium::Value;
+
+/// A CBOR map under construction, keyed by the small integers CTAP2 uses.
+#[derive(Default)]
+pub struct MapBuilder(Vec<(Value, Value)>);
+
+impl MapBuilder {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn entry(mut self, key: i128, value: impl Into<Value>) -> Self {
+        self.0.push((Value::Integer(key.into()), value.into()));
+        self
+    }
+
+    pub fn maybe_entry(self, key: i128, value: Option<impl Into<Value>>) -> Self {
+        match value {
+            Some(value) => self.entry(key, value),
+            None => self,
+        }
+    }
+
+    pub fn build(self) -> Value {
+        Value::Map(self.0)
+    }
+
+    pub fn into_params(self) -> Option<Value> {
+        if self.0.is_empty() {
+            None
+        } else {
+            Some(self.build())
+        }
+    }
+}
+
+/// Read access into a response's top-level CBOR map.
+pub trait ValueExt {
+    fn map_get(&self, key: i128) -> Option<&Value>;
+    fn map_get_text(&self, key: &str) -> Option<&Value>;
+    fn as_bytes(&self) -> Option<&[u8]>;
+    fn as_text_owned(&self) -> Option<String>;
+}
+
+impl ValueExt for Value {
+    fn map_get(&self, key: i128) -> Option<&Value> {
+        let Value::Map(entries) = self else {
+            return None;
+        };
+        entries.iter().find_map(|(k, v)| {
+            if k == &Value::Integer(key.into()) {
+                Some(v)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn map_get_text(&self, key: &str) -> Option<&Value> {
+        let Value::Map(entries) = self else {
+            return None;
+        };
+        entries.iter().find_map(|(k, v)| {
+            if k.as_text() == Some(key) {
+                Some(v)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    fn as_text_owned(&self) -> Option<String> {
+        match self {
+            Value::Text(text) => Some(text.clone()),
+            _ => None,
+        }
+    }
+}
+
+pub fn empty_map_as_none(value: Value) -> Option<Value> {
+    match &value {
+        Value::Map(entries) if entries.is_empty() => None,
+        _ => Some(value),
+    }
+}
@@ -0,0 +1,81 @@
+//! `authenticatorClientPIN` (0x06).
+
+use ciborium::Value;
+
+use super::cbor_util::{MapBuilder, ValueExt};
+use super::crypto::PublicKey;
+use super::Ctap2Request;
+
+pub struct ClientPin {
+    pub pin_protocol: u8,
+    pub subcommand: u8,
+    pub key_agreement: Option<PublicKey>,
+    pub pin_auth: Option<Vec<u8>>,
+    pub new_pin_enc: Option<Vec<u8>>,
+    pub pin_hash_enc: Option<Vec<u8>>,
+    pub permissions: Option<u8>,
+    pub rp_id: Option<String>,
+}
+
+impl ClientPin {
+    pub fn new(pin_protocol: u8, subcommand: u8) -> Self {
+        Self {
+            pin_protocol,
+            subcommand,
+            key_agreement: None,
+            pin_auth: None,
+            new_pin_enc: None,
+            pin_hash_enc: None,
+            permissions: None,
+            rp_id: None,
+        }
+    }
+}
+
+impl Ctap2Request for ClientPin {
+    type Reply = ClientPinReply;
+    const CMD: u8 = 0x06;
+
+    fn params(&self) -> Option<Value> {
+        MapBuilder::new()
+            .entry(0x01, self.pin_protocol as i128)
+            .entry(0x02, self.subcommand as i128)
+            .maybe_entry(0x03, self.key_agreement.as_ref().map(PublicKey::to_cbor))
+            .maybe_entry(0x04, self.pin_auth.clone())
+            .maybe_entry(0x05, self.new_pin_enc.clone())
+            .maybe_entry(0x06, self.pin_hash_enc.clone())
+            .maybe_entry(0x09, self.permissions.map(|p| p as i128))
+            .maybe_entry(0x0a, self.rp_id.clone())
+            .into_params()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ClientPinReply {
+    pub key_agreement: Option<Value>,
+    pub pin_token: Option<Value>,
+    pub pin_retries: Option<u32>,
+    pub power_cycle_state: Option<bool>,
+    pub uv_retries: Option<u32>,
+}
+
+impl ClientPinReply {
+    pub(crate) fn parse(value: Option<Value>) -> Self {
+        let Some(value) = value else {
+            return Self::default();
+        };
+        Self {
+            key_agreement: value.map_get(0x01).cloned(),
+            pin_token: value.map_get(0x02).cloned(),
+            pin_retries: value
+                .map_get(0x03)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+            power_cycle_state: value.map_get(0x04).and_then(Value::as_bool),
+            uv_retries: value
+                .map_get(0x05)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! `authenticatorCredentialManagement` (0x0A). Only the subset of
+//! subcommands exercised by the test suite (`enumerateRPsBegin`,
+//! `enumerateCredentialsBegin`) is implemented.
+
+use ciborium::Value;
+
+use super::cbor_util::{MapBuilder, ValueExt};
+use super::get_assertion::PubKeyCredDescriptor;
+use super::Ctap2Request;
+
+#[derive(Clone, Debug, Default)]
+pub struct CredentialManagementParams {
+    pub rp_id_hash: Option<Vec<u8>>,
+    pub credential_id: Option<PubKeyCredDescriptor>,
+}
+
+impl CredentialManagementParams {
+    pub(crate) fn to_cbor(&self) -> Value {
+        MapBuilder::new()
+            .maybe_entry(0x01, self.rp_id_hash.clone())
+            .maybe_entry(0x02, self.credential_id.as_ref().map(PubKeyCredDescriptor::to_cbor))
+            .build()
+    }
+
+    /// The CBOR-encoded `subCommandParams`, as covered by `pinAuth`.
+    pub fn serialized(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        ciborium::ser::into_writer(&self.to_cbor(), &mut buf).unwrap();
+        buf
+    }
+}
+
+pub struct CredentialManagement {
+    pub subcommand: u8,
+    pub subcommand_params: Option<CredentialManagementParams>,
+    pub pin_protocol: Option<u8>,
+    pub pin_auth: Option<Vec<u8>>,
+}
+
+impl Ctap2Request for CredentialManagement {
+    type Reply = CredentialManagementReply;
+    const CMD: u8 = 0x0a;
+
+    fn params(&self) -> Option<Value> {
+        MapBuilder::new()
+            .entry(0x01, self.subcommand as i128)
+            .maybe_entry(
+                0x02,
+                self.subcommand_params.as_ref().map(CredentialManagementParams::to_cbor),
+            )
+            .maybe_entry(0x03, self.pin_protocol.map(|p| p as i128))
+            .maybe_entry(0x04, self.pin_auth.clone())
+            .into_params()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CredentialManagementReply {
+    pub existing_resident_credentials_count: Option<u32>,
+    pub max_possible_remaining_resident_credentials_count: Option<u32>,
+    pub rp: Option<Value>,
+    pub rp_id_hash: Option<Value>,
+    pub total_rps: Option<u32>,
+    pub user: Option<Value>,
+    pub credential_id: Option<PubKeyCredDescriptor>,
+    pub total_credentials: Option<u32>,
+    pub third_party_payment: Option<bool>,
+}
+
+impl CredentialManagementReply {
+    pub(crate) fn parse(value: Option<Value>) -> Self {
+        let Some(value) = value else {
+            return Self::default();
+        };
+        Self {
+            existing_resident_credentials_count: value
+                .map_get(0x01)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+            max_possible_remaining_resident_credentials_count: value
+                .map_get(0x02)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+            rp: value.map_get(0x03).cloned(),
+            rp_id_hash: value.map_get(0x04).cloned(),
+            total_rps: value
+                .map_get(0x05)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+            user: value.map_get(0x06).cloned(),
+            credential_id: value.map_get(0x07).map(PubKeyCredDescriptor::parse),
+            total_credentials: value
+                .map_get(0x09)
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+            third_party_payment: value.map_get(0x64).and_then(Value::as_bool),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! `pinUvAuthProtocol` one and two key-agreement and encryption primitives, plus the
+//! platform-side helper (`KeyAgreementKey`) the test client uses to stand in
+//! for a WebAuthn client during PIN/UV flows.
+
+use ciborium::Value;
+use hmac::{Hmac, Mac};
+use p256::ecdh::diffie_hellman;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{EncodedPoint, PublicKey as P256PublicKey, SecretKey};
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+
+use super::cbor_util::{MapBuilder, ValueExt};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// An authenticator (or platform) COSE_Key-style EC2 public key, as carried
+/// on the wire inside `authenticatorClientPIN` requests and responses.
+#[derive(Clone, Debug)]
+pub struct PublicKey {
+    pub x: [u8; 32],
+    pub y: [u8; 32],
+}
+
+impl PublicKey {
+    pub(crate) fn to_encoded_point(&self) -> EncodedPoint {
+        EncodedPoint::from_affine_coordinates((&self.x).into(), (&self.y).into(), false)
+    }
+
+    pub(crate) fn from_p256(public: &P256PublicKey) -> Self {
+        let point = public.to_encoded_point(false);
+        let mut x = [0u8; 32];
+        let mut y = [0u8; 32];
+        x.copy_from_slice(point.x().unwrap());
+        y.copy_from_slice(point.y().unwrap());
+        Self { x, y }
+    }
+
+    pub(crate) fn to_cbor(&self) -> Value {
+        MapBuilder::new()
+            .entry(1, 2i128) // kty: EC2
+            .entry(3, -25i128) // alg: ECDH-ES+HKDF-256 (placeholder, unused by authenticator)
+            .entry(-1, -1i128) // crv: P-256
+            .entry(-2, self.x.to_vec())
+            .entry(-3, self.y.to_vec())
+            .build()
+    }
+}
+
+impl From<Value> for PublicKey {
+    fn from(value: Value) -> Self {
+        let x = value.map_get(-2).and_then(ValueExt::as_bytes).unwrap();
+        let y = value.map_get(-3).and_then(ValueExt::as_bytes).unwrap();
+        let mut this = PublicKey { x: [0; 32], y: [0; 32] };
+        this.x.copy_from_slice(x);
+        this.y.copy_from_slice(y);
+        this
+    }
+}
+
+/// Platform-side ephemeral key-agreement keypair, generated fresh for every
+/// `authenticatorClientPIN` exchange.
+pub struct KeyAgreementKey {
+    secret: SecretKey,
+}
+
+impl KeyAgreementKey {
+    pub fn generate() -> Self {
+        Self {
+            secret: SecretKey::random(&mut OsRng),
+        }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey::from_p256(&self.secret.public_key())
+    }
+
+    /// Perform ECDH with the authenticator's key-agreement key and derive the
+    /// `pinUvAuthProtocol` shared secret for `protocol` (one or two).
+    /// Protocol one is `SHA-256(Z)`, used as both the AES and HMAC key;
+    /// protocol two derives two independent 32-byte keys via HKDF-SHA256.
+    pub fn shared_secret(&self, authenticator_key: &PublicKey, protocol: u8) -> SharedSecret {
+        let peer_point = authenticator_key.to_encoded_point();
+        let peer_public = P256PublicKey::from_sec1_bytes(peer_point.as_bytes()).unwrap();
+        let shared = diffie_hellman(self.secret.to_nonzero_scalar(), peer_public.as_affine());
+        let z = shared.raw_secret_bytes();
+
+        if protocol == 1 {
+            let key: [u8; 32] = Sha256::digest(z.as_slice()).into();
+            return SharedSecret {
+                hmac_key: key,
+                aes_key: key,
+                protocol,
+            };
+        }
+
+        // HKDF-SHA256 with an empty salt, per pinUvAuthProtocol two: derive
+        // the HMAC key and AES key as two separate "info" expansions.
+        let hk = hkdf::Hkdf::<Sha256>::new(Some(&[0u8; 32]), z.as_slice());
+        let mut hmac_key = [0u8; 32];
+        let mut aes_key = [0u8; 32];
+        hk.expand(b"CTAP2 HMAC key", &mut hmac_key).unwrap();
+        hk.expand(b"CTAP2 AES key", &mut aes_key).unwrap();
+
+        SharedSecret { hmac_key, aes_key, protocol }
+    }
+}
+
+/// The symmetric keys derived from an ECDH key-agreement handshake.
+pub struct SharedSecret {
+    hmac_key: [u8; 32],
+    aes_key: [u8; 32],
+    protocol: u8,
+}
+
+impl SharedSecret {
+    /// `aes-256-cbc(IV, key, demPlaintext)`. Protocol two prepends a random
+    /// 16-byte IV to the ciphertext; protocol one uses a fixed all-zero IV
+    /// and prepends nothing.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        use rand_core::RngCore;
+
+        assert_eq!(
+            plaintext.len() % 16,
+            0,
+            "pinUvAuthProtocol plaintexts are block-aligned"
+        );
+
+        if self.protocol == 1 {
+            return cbc_encrypt(&self.aes_key, &[0u8; 16], plaintext);
+        }
+
+        let mut iv = [0u8; 16];
+        OsRng.fill_bytes(&mut iv);
+
+        let mut out = iv.to_vec();
+        out.extend(cbc_encrypt(&self.aes_key, &iv, plaintext));
+        out
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Vec<u8> {
+        if self.protocol == 1 {
+            return cbc_decrypt(&self.aes_key, &[0u8; 16], data);
+        }
+        let (iv, ciphertext) = data.split_at(16);
+        cbc_decrypt(&self.aes_key, iv, ciphertext)
+    }
+
+    /// `HMAC-SHA256(hmacKey, message)`: the full 32-byte tag for protocol
+    /// two, truncated to the first 16 bytes for protocol one.
+    pub fn authenticate(&self, message: &[u8]) -> Vec<u8> {
+        let mut mac = HmacSha256::new_from_slice(&self.hmac_key).unwrap();
+        mac.update(message);
+        let tag = mac.finalize().into_bytes().to_vec();
+        if self.protocol == 1 {
+            tag[..16].to_vec()
+        } else {
+            tag
+        }
+    }
+
+    pub fn decrypt_pin_token(&self, encrypted_pin_token: &[u8]) -> PinToken {
+        let bytes = self.decrypt(encrypted_pin_token);
+        let mut token = [0u8; 32];
+        token.copy_from_slice(&bytes);
+        PinToken {
+            token,
+            protocol: self.protocol,
+        }
+    }
+}
+
+fn cbc_encrypt(key: &[u8; 32], iv: &[u8; 16], plaintext: &[u8]) -> Vec<u8> {
+    use aes::cipher::{BlockEncrypt, KeyInit};
+    let cipher = aes::Aes256::new(key.into());
+    let mut prev = *iv;
+    let mut out = Vec::with_capacity(plaintext.len());
+    for chunk in plaintext.chunks(16) {
+        let mut block = [0u8; 16];
+        block.copy_from_slice(chunk);
+        for i in 0..16 {
+            block[i] ^= prev[i];
+        }
+        cipher.encrypt_block((&mut block).into());
+        out.extend_from_slice(&block);
+        prev = block;
+    }
+    out
+}
+
+fn cbc_decrypt(key: &[u8; 32], iv: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    use aes::cipher::{BlockDecrypt, KeyInit};
+    let cipher = aes::Aes256::new(key.into());
+    let mut prev = [0u8; 16];
+    prev.copy_from_slice(iv);
+    let mut out = Vec::with_capacity(ciphertext.len());
+    for chunk in ciphertext.chunks(16) {
+        let mut block = [0u8; 16];
+        block.copy_from_slice(chunk);
+        let ciphertext_block = block;
+        cipher.decrypt_block((&mut block).into());
+        for i in 0..16 {
+            block[i] ^= prev[i];
+        }
+        out.extend_from_slice(&block);
+        prev = ciphertext_block;
+    }
+    out
+}
+
+/// A decrypted `pinUvAuthToken`, used to authenticate subsequent requests.
+pub struct PinToken {
+    token: [u8; 32],
+    protocol: u8,
+}
+
+impl PinToken {
+    /// `HMAC-SHA256(pinUvAuthToken, message)`: the full 32-byte `pinAuth`
+    /// for protocol two, truncated to the first 16 bytes for protocol one.
+    pub fn authenticate(&self, message: &[u8]) -> Vec<u8> {
+        let mut mac = HmacSha256::new_from_slice(&self.token).unwrap();
+        mac.update(message);
+        let tag = mac.finalize().into_bytes().to_vec();
+        if self.protocol == 1 {
+            tag[..16].to_vec()
+        } else {
+            tag
+        }
+    }
+
+    pub(crate) fn sha256_of_token(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.token);
+        hasher.finalize().into()
+    }
+}
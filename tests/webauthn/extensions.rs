@@ -0,0 +1,31 @@
+//! The extensions this fork's test client understands: the non-standard
+//! `thirdPartyPayment` boolean extension, carried verbatim on both
+//! `MakeCredential` and `GetAssertion` requests and echoed back in
+//! `authData.extensions`; and the CTAP2.1 `minPinLength` boolean extension,
+//! a `MakeCredential`-only request for the authenticator's current minimum
+//! PIN length, gated by `authenticatorConfig`'s `setMinPinLength`
+//! `minPinLengthRPIDs` allowlist.
+
+use ciborium::Value;
+
+#[derive(Clone, Debug, Default)]
+pub struct ExtensionsInput {
+    pub third_party_payment: Option<bool>,
+    pub min_pin_length: Option<bool>,
+}
+
+impl ExtensionsInput {
+    pub(crate) fn to_cbor(&self) -> Value {
+        let mut entries = Vec::new();
+        if let Some(third_party_payment) = self.third_party_payment {
+            entries.push((
+                Value::Text("thirdPartyPayment".into()),
+                Value::Bool(third_party_payment),
+            ));
+        }
+        if let Some(min_pin_length) = self.min_pin_length {
+            entries.push((Value::Text("minPinLength".into()), Value::Bool(min_pin_length)));
+        }
+        Value::Map(entries)
+    }
+}
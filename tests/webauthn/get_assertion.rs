@@ -0,0 +1,127 @@
+//! `authenticatorGetAssertion` (0x02).
+
+use ciborium::Value;
+
+use super::auth_data::AuthData;
+use super::cbor_util::{MapBuilder, ValueExt};
+use super::extensions::ExtensionsInput;
+use super::Ctap2Request;
+
+#[derive(Clone, Debug)]
+pub struct PubKeyCredDescriptor {
+    pub ty: String,
+    pub id: Vec<u8>,
+}
+
+impl PubKeyCredDescriptor {
+    pub fn new(ty: impl Into<String>, id: Vec<u8>) -> Self {
+        Self { ty: ty.into(), id }
+    }
+
+    pub(crate) fn to_cbor(&self) -> Value {
+        Value::Map(vec![
+            (Value::Text("type".into()), Value::Text(self.ty.clone())),
+            (Value::Text("id".into()), Value::Bytes(self.id.clone())),
+        ])
+    }
+
+    pub(crate) fn parse(value: &Value) -> Self {
+        Self {
+            ty: value.map_get_text("type").and_then(ValueExt::as_text_owned).unwrap(),
+            id: value
+                .map_get_text("id")
+                .and_then(ValueExt::as_bytes)
+                .unwrap()
+                .to_vec(),
+        }
+    }
+}
+
+pub struct GetAssertion {
+    pub rp_id: String,
+    pub client_data_hash: Vec<u8>,
+    pub allow_list: Option<Vec<PubKeyCredDescriptor>>,
+    pub extensions: Option<ExtensionsInput>,
+    pub pin_auth: Option<Vec<u8>>,
+    pub pin_protocol: Option<u8>,
+}
+
+impl GetAssertion {
+    pub fn new(rp_id: impl Into<String>, client_data_hash: impl Into<Vec<u8>>) -> Self {
+        Self {
+            rp_id: rp_id.into(),
+            client_data_hash: client_data_hash.into(),
+            allow_list: None,
+            extensions: None,
+            pin_auth: None,
+            pin_protocol: None,
+        }
+    }
+}
+
+impl Ctap2Request for GetAssertion {
+    type Reply = GetAssertionReply;
+    const CMD: u8 = 0x02;
+
+    fn params(&self) -> Option<Value> {
+        let allow_list = self.allow_list.as_ref().map(|list| {
+            Value::Array(list.iter().map(PubKeyCredDescriptor::to_cbor).collect())
+        });
+        MapBuilder::new()
+            .entry(0x01, self.rp_id.clone())
+            .entry(0x02, Value::Bytes(self.client_data_hash.clone()))
+            .maybe_entry(0x03, allow_list)
+            .maybe_entry(
+                0x04,
+                self.extensions.as_ref().map(ExtensionsInput::to_cbor),
+            )
+            .maybe_entry(0x06, self.pin_auth.clone())
+            .maybe_entry(0x07, self.pin_protocol.map(|p| p as i128))
+            .into_params()
+    }
+}
+
+#[derive(Debug)]
+pub struct GetAssertionReply {
+    pub credential: PubKeyCredDescriptor,
+    pub auth_data: AuthData,
+    pub signature: Vec<u8>,
+    /// The `id` of the matched user entity (key `0x04`), present when the
+    /// authenticator had to disambiguate between several accounts.
+    pub user_id: Option<Vec<u8>>,
+    pub number_of_credentials: Option<u32>,
+}
+
+impl GetAssertionReply {
+    pub(crate) fn parse(value: Option<Value>) -> Self {
+        let value = value.expect("getAssertion reply must not be empty");
+        let credential = PubKeyCredDescriptor::parse(value.map_get(0x01).unwrap());
+        let auth_data = AuthData::parse(
+            value
+                .map_get(0x02)
+                .and_then(ValueExt::as_bytes)
+                .expect("authData must be present"),
+        );
+        let signature = value
+            .map_get(0x03)
+            .and_then(ValueExt::as_bytes)
+            .unwrap()
+            .to_vec();
+        let user_id = value
+            .map_get(0x04)
+            .and_then(|user| user.map_get_text("id"))
+            .and_then(ValueExt::as_bytes)
+            .map(<[u8]>::to_vec);
+        let number_of_credentials = value
+            .map_get(0x05)
+            .and_then(Value::as_integer)
+            .map(|n| n.try_into().unwrap());
+        Self {
+            credential,
+            auth_data,
+            signature,
+            user_id,
+            number_of_credentials,
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! `authenticatorGetInfo` (0x04).
+
+use ciborium::Value;
+
+use super::cbor_util::ValueExt;
+use super::Ctap2Request;
+
+/// `authenticatorGetInfo` takes no parameters.
+pub struct GetInfo;
+
+impl Ctap2Request for GetInfo {
+    type Reply = GetInfoReply;
+    const CMD: u8 = 0x04;
+
+    fn params(&self) -> Option<Value> {
+        None
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GetInfoReply {
+    pub versions: Vec<String>,
+    pub extensions: Option<Vec<String>>,
+    pub aaguid: Value,
+    pub options: Option<Value>,
+    pub max_msg_size: Option<u32>,
+    pub pin_protocols: Option<Vec<u8>>,
+    pub attestation_formats: Option<Vec<String>>,
+    pub uv_modality: Option<u32>,
+    pub min_pin_length: Option<u32>,
+    pub max_serialized_large_blob_array: Option<u32>,
+}
+
+impl GetInfoReply {
+    pub(crate) fn parse(value: Option<Value>) -> Self {
+        let value = value.unwrap_or(Value::Map(Vec::new()));
+        let versions = value
+            .map_get(0x01)
+            .and_then(Value::as_array)
+            .map(|versions| {
+                versions
+                    .iter()
+                    .filter_map(ValueExt::as_text_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let extensions = value.map_get(0x02).and_then(Value::as_array).map(|exts| {
+            exts.iter()
+                .filter_map(ValueExt::as_text_owned)
+                .collect()
+        });
+        let aaguid = value.map_get(0x03).cloned().unwrap_or(Value::Null);
+        let options = value.map_get(0x04).cloned();
+        let max_msg_size = value
+            .map_get(0x05)
+            .and_then(Value::as_integer)
+            .map(|n| n.try_into().unwrap());
+        let pin_protocols = value.map_get(0x06).and_then(Value::as_array).map(|ps| {
+            ps.iter()
+                .filter_map(Value::as_integer)
+                .map(|n| n.try_into().unwrap())
+                .collect()
+        });
+        let attestation_formats = value.map_get(0x16).and_then(Value::as_array).map(|fmts| {
+            fmts.iter()
+                .filter_map(ValueExt::as_text_owned)
+                .collect()
+        });
+        let uv_modality = value
+            .map_get(0x0e)
+            .and_then(Value::as_integer)
+            .map(|n| n.try_into().unwrap());
+        let min_pin_length = value
+            .map_get(0x15)
+            .and_then(Value::as_integer)
+            .map(|n| n.try_into().unwrap());
+        let max_serialized_large_blob_array = value
+            .map_get(0x0b)
+            .and_then(Value::as_integer)
+            .map(|n| n.try_into().unwrap());
+        Self {
+            versions,
+            extensions,
+            aaguid,
+            options,
+            max_msg_size,
+            pin_protocols,
+            attestation_formats,
+            uv_modality,
+            min_pin_length,
+            max_serialized_large_blob_array,
+        }
+    }
+
+    /// Looks up a boolean entry (e.g. `rk`, `uv`, `bioEnroll`) in the
+    /// `options` map, if present.
+    pub fn option(&self, name: &str) -> Option<bool> {
+        self.options.as_ref().and_then(|options| {
+            options
+                .map_get_text(name)
+                .and_then(|value| value.as_bool())
+        })
+    }
+}
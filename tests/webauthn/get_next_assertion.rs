@@ -0,0 +1,19 @@
+//! `authenticatorGetNextAssertion` (0x08).
+
+use ciborium::Value;
+
+use super::get_assertion::GetAssertionReply;
+use super::Ctap2Request;
+
+/// `authenticatorGetNextAssertion` takes no parameters; it pops the next
+/// credential queued by a preceding `authenticatorGetAssertion` call.
+pub struct GetNextAssertion;
+
+impl Ctap2Request for GetNextAssertion {
+    type Reply = GetAssertionReply;
+    const CMD: u8 = 0x08;
+
+    fn params(&self) -> Option<Value> {
+        None
+    }
+}
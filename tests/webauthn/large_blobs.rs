@@ -0,0 +1,89 @@
+//! `authenticatorLargeBlobs` (0x0C): offset-addressed reads and writes of
+//! the serialized `largeBlobArray`.
+
+use ciborium::Value;
+
+use super::cbor_util::{MapBuilder, ValueExt};
+use super::Ctap2Request;
+
+pub struct LargeBlobs {
+    pub get: Option<u32>,
+    pub set: Option<Vec<u8>>,
+    pub offset: u32,
+    pub length: Option<u32>,
+    pub pin_auth: Option<Vec<u8>>,
+    pub pin_protocol: Option<u8>,
+}
+
+impl LargeBlobs {
+    /// Reads up to `count` bytes of the serialized large-blob array,
+    /// starting at `offset`. Unauthenticated, per spec.
+    pub fn get(offset: u32, count: u32) -> Self {
+        Self {
+            get: Some(count),
+            set: None,
+            offset,
+            length: None,
+            pin_auth: None,
+            pin_protocol: None,
+        }
+    }
+
+    /// Writes `fragment` at `offset`. `length` (the total serialized
+    /// array size) must be set on the first fragment (`offset == 0`) only.
+    pub fn set(offset: u32, fragment: Vec<u8>) -> Self {
+        Self {
+            get: None,
+            set: Some(fragment),
+            offset,
+            length: None,
+            pin_auth: None,
+            pin_protocol: None,
+        }
+    }
+
+    /// The message covered by `pinUvAuthParam`:
+    /// `0xff*32 || 0x0c || 0x00 || offset(4 bytes LE) || SHA-256(fragment)`.
+    pub fn auth_message(offset: u32, fragment: &[u8]) -> Vec<u8> {
+        use sha2::{Digest, Sha256};
+
+        let mut message = vec![0xff; 32];
+        message.push(0x0c);
+        message.push(0x00);
+        message.extend_from_slice(&offset.to_le_bytes());
+        message.extend_from_slice(&Sha256::digest(fragment));
+        message
+    }
+}
+
+impl Ctap2Request for LargeBlobs {
+    type Reply = LargeBlobsReply;
+    const CMD: u8 = 0x0c;
+
+    fn params(&self) -> Option<Value> {
+        MapBuilder::new()
+            .maybe_entry(0x01, self.get.map(|n| n as i128))
+            .maybe_entry(0x02, self.set.clone())
+            .entry(0x03, self.offset as i128)
+            .maybe_entry(0x04, self.length.map(|n| n as i128))
+            .maybe_entry(0x05, self.pin_auth.clone())
+            .maybe_entry(0x06, self.pin_protocol.map(|p| p as i128))
+            .into_params()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LargeBlobsReply {
+    pub config: Option<Vec<u8>>,
+}
+
+impl LargeBlobsReply {
+    pub(crate) fn parse(value: Option<Value>) -> Self {
+        let Some(value) = value else {
+            return Self::default();
+        };
+        Self {
+            config: value.map_get(0x01).and_then(ValueExt::as_bytes).map(<[u8]>::to_vec),
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! `authenticatorMakeCredential` (0x01) and the relying-party/user/credential
+//! types it shares with the rest of the `webauthn` module.
+
+use ciborium::Value;
+
+use super::auth_data::AuthData;
+use super::cbor_util::{MapBuilder, ValueExt};
+use super::extensions::ExtensionsInput;
+use super::Ctap2Request;
+
+#[derive(Clone, Debug)]
+pub struct Rp {
+    pub id: String,
+    pub name: Option<String>,
+}
+
+impl Rp {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            name: None,
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct User {
+    pub id: Vec<u8>,
+    pub name: Option<String>,
+    pub display_name: Option<String>,
+}
+
+impl User {
+    pub fn new(id: impl Into<Vec<u8>>) -> Self {
+        Self {
+            id: id.into(),
+            name: None,
+            display_name: None,
+        }
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn display_name(mut self, display_name: impl Into<String>) -> Self {
+        self.display_name = Some(display_name.into());
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PubKeyCredParam {
+    pub ty: String,
+    pub alg: i32,
+}
+
+impl PubKeyCredParam {
+    pub fn new(ty: impl Into<String>, alg: i32) -> Self {
+        Self {
+            ty: ty.into(),
+            alg,
+        }
+    }
+
+    fn to_cbor(&self) -> Value {
+        Value::Map(vec![
+            (Value::Text("type".into()), Value::Text(self.ty.clone())),
+            (Value::Text("alg".into()), Value::Integer(self.alg.into())),
+        ])
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MakeCredentialOptions {
+    pub rk: Option<bool>,
+    pub uv: Option<bool>,
+}
+
+impl MakeCredentialOptions {
+    pub fn rk(mut self, rk: bool) -> Self {
+        self.rk = Some(rk);
+        self
+    }
+
+    pub fn uv(mut self, uv: bool) -> Self {
+        self.uv = Some(uv);
+        self
+    }
+
+    pub(crate) fn to_cbor(&self) -> Value {
+        let mut entries = Vec::new();
+        if let Some(rk) = self.rk {
+            entries.push((Value::Text("rk".into()), Value::Bool(rk)));
+        }
+        if let Some(uv) = self.uv {
+            entries.push((Value::Text("uv".into()), Value::Bool(uv)));
+        }
+        Value::Map(entries)
+    }
+}
+
+pub struct MakeCredential {
+    pub client_data_hash: Vec<u8>,
+    pub rp: Rp,
+    pub user: User,
+    pub pub_key_cred_params: Vec<PubKeyCredParam>,
+    pub exclude_list: Option<Vec<super::get_assertion::PubKeyCredDescriptor>>,
+    pub extensions: Option<ExtensionsInput>,
+    pub options: Option<MakeCredentialOptions>,
+    pub pin_auth: Option<Vec<u8>>,
+    pub pin_protocol: Option<u8>,
+    pub attestation_formats_preference: Option<Vec<&'static str>>,
+}
+
+impl MakeCredential {
+    pub fn new(
+        client_data_hash: impl Into<Vec<u8>>,
+        rp: Rp,
+        user: User,
+        pub_key_cred_params: Vec<PubKeyCredParam>,
+    ) -> Self {
+        Self {
+            client_data_hash: client_data_hash.into(),
+            rp,
+            user,
+            pub_key_cred_params,
+            exclude_list: None,
+            extensions: None,
+            options: None,
+            pin_auth: None,
+            pin_protocol: None,
+            attestation_formats_preference: None,
+        }
+    }
+
+    fn rp_cbor(&self) -> Value {
+        let mut entries = vec![(Value::Text("id".into()), Value::Text(self.rp.id.clone()))];
+        if let Some(name) = &self.rp.name {
+            entries.push((Value::Text("name".into()), Value::Text(name.clone())));
+        }
+        Value::Map(entries)
+    }
+
+    fn user_cbor(&self) -> Value {
+        let mut entries = vec![(
+            Value::Text("id".into()),
+            Value::Bytes(self.user.id.clone()),
+        )];
+        if let Some(name) = &self.user.name {
+            entries.push((Value::Text("name".into()), Value::Text(name.clone())));
+        }
+        if let Some(display_name) = &self.user.display_name {
+            entries.push((
+                Value::Text("displayName".into()),
+                Value::Text(display_name.clone()),
+            ));
+        }
+        Value::Map(entries)
+    }
+}
+
+impl Ctap2Request for MakeCredential {
+    type Reply = MakeCredentialReply;
+    const CMD: u8 = 0x01;
+
+    fn params(&self) -> Option<Value> {
+        let pub_key_cred_params = Value::Array(
+            self.pub_key_cred_params
+                .iter()
+                .map(PubKeyCredParam::to_cbor)
+                .collect(),
+        );
+        let exclude_list = self.exclude_list.as_ref().map(|list| {
+            Value::Array(
+                list.iter()
+                    .map(super::get_assertion::PubKeyCredDescriptor::to_cbor)
+                    .collect(),
+            )
+        });
+        let attestation_formats_preference = self.attestation_formats_preference.as_ref().map(|fmts| {
+            Value::Array(fmts.iter().map(|f| Value::Text((*f).into())).collect())
+        });
+        MapBuilder::new()
+            .entry(0x01, Value::Bytes(self.client_data_hash.clone()))
+            .entry(0x02, self.rp_cbor())
+            .entry(0x03, self.user_cbor())
+            .entry(0x04, pub_key_cred_params)
+            .maybe_entry(0x05, exclude_list)
+            .maybe_entry(
+                0x06,
+                self.extensions.as_ref().map(ExtensionsInput::to_cbor),
+            )
+            .maybe_entry(0x07, self.options.as_ref().map(MakeCredentialOptions::to_cbor))
+            .maybe_entry(0x08, self.pin_auth.clone())
+            .maybe_entry(0x09, self.pin_protocol.map(|p| p as i128))
+            .maybe_entry(0x0b, attestation_formats_preference)
+            .into_params()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttStmtFormat {
+    Packed,
+    None,
+}
+
+impl AttStmtFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Packed => "packed",
+            Self::None => "none",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AttStmt {
+    pub alg: Option<i32>,
+    pub sig: Option<Vec<u8>>,
+    pub x5c: Option<Vec<Vec<u8>>>,
+}
+
+impl AttStmt {
+    pub(crate) fn parse(value: &Value) -> Self {
+        Self {
+            alg: value
+                .map_get_text("alg")
+                .and_then(Value::as_integer)
+                .map(|n| n.try_into().unwrap()),
+            sig: value
+                .map_get_text("sig")
+                .and_then(ValueExt::as_bytes)
+                .map(<[u8]>::to_vec),
+            x5c: value.map_get_text("x5c").and_then(Value::as_array).map(|certs| {
+                certs
+                    .iter()
+                    .filter_map(ValueExt::as_bytes)
+                    .map(<[u8]>::to_vec)
+                    .collect()
+            }),
+        }
+    }
+
+    /// Structurally validates the attestation statement for `format`: for
+    /// `packed`, that the algorithm is ES256 and that the signature and the
+    /// first certificate are present and start with a DER SEQUENCE tag
+    /// (neither is fully parsed, and the signature is not verified); for
+    /// `none`, that the statement is empty.
+    pub fn validate(&self, format: AttStmtFormat, _auth_data: &AuthData) {
+        match format {
+            AttStmtFormat::None => {
+                assert!(self.alg.is_none() && self.sig.is_none() && self.x5c.is_none());
+            }
+            AttStmtFormat::Packed => {
+                assert_eq!(self.alg, Some(-7), "packed attestation must use ES256");
+                let sig = self.sig.as_ref().expect("packed attStmt must carry sig");
+                assert_eq!(sig[0], 0x30, "sig must be a DER ECDSA signature");
+                let x5c = self.x5c.as_ref().expect("packed attStmt must carry x5c");
+                let cert = &x5c[0];
+                assert_eq!(cert[0], 0x30, "x5c[0] must be a DER certificate");
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MakeCredentialReply {
+    pub fmt: String,
+    pub auth_data: AuthData,
+    pub att_stmt: Option<AttStmt>,
+    pub ep_att: Option<bool>,
+    pub large_blob_key: Option<Vec<u8>>,
+}
+
+impl MakeCredentialReply {
+    pub(crate) fn parse(value: Option<Value>) -> Self {
+        let value = value.expect("makeCredential reply must not be empty");
+        let fmt = value.map_get(0x01).and_then(ValueExt::as_text_owned).unwrap();
+        let auth_data = AuthData::parse(
+            value
+                .map_get(0x02)
+                .and_then(ValueExt::as_bytes)
+                .expect("authData must be present"),
+        );
+        let att_stmt = value.map_get(0x03).map(AttStmt::parse);
+        let ep_att = value.map_get(0x04).and_then(Value::as_bool);
+        let large_blob_key = value
+            .map_get(0x05)
+            .and_then(ValueExt::as_bytes)
+            .map(<[u8]>::to_vec);
+        Self {
+            fmt,
+            auth_data,
+            att_stmt,
+            ep_att,
+            large_blob_key,
+        }
+    }
+}
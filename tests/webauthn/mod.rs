@@ -0,0 +1,127 @@
+//! CBOR request/response types for the CTAP2 commands exercised by the
+//! integration tests. This module is deliberately independent from any
+//! production authenticator implementation: it is the *client* side of the
+//! protocol, used by [`crate::virt`] to drive the virtual authenticator.
+
+mod auth_data;
+mod authenticator_config;
+mod bio_enrollment;
+mod cbor_util;
+mod client_pin;
+mod credential_management;
+mod crypto;
+mod extensions;
+mod get_assertion;
+mod get_info;
+mod get_next_assertion;
+mod large_blobs;
+mod make_credential;
+mod reset;
+mod selection;
+pub mod u2f;
+
+use ciborium::Value;
+
+pub use auth_data::{AuthData, Credential};
+pub use authenticator_config::{
+    AuthenticatorConfig, AuthenticatorConfigParams, AuthenticatorConfigReply,
+    SUBCOMMAND_ENABLE_ENTERPRISE_ATTESTATION, SUBCOMMAND_SET_MIN_PIN_LENGTH,
+    SUBCOMMAND_TOGGLE_ALWAYS_UV,
+};
+pub use bio_enrollment::{
+    BioEnrollment, BioEnrollmentParams, BioEnrollmentReply, ENROLL_SAMPLE_STATUS_GOOD,
+    SUBCOMMAND_CANCEL_CURRENT_ENROLLMENT, SUBCOMMAND_ENROLL_BEGIN,
+    SUBCOMMAND_ENROLL_CAPTURE_NEXT_SAMPLE, SUBCOMMAND_ENUMERATE_ENROLLMENTS,
+    SUBCOMMAND_GET_FINGERPRINT_SENSOR_INFO, SUBCOMMAND_REMOVE_ENROLLMENT,
+    SUBCOMMAND_SET_FRIENDLY_NAME,
+};
+pub use client_pin::{ClientPin, ClientPinReply};
+pub use credential_management::{
+    CredentialManagement, CredentialManagementParams, CredentialManagementReply,
+};
+pub use crypto::{KeyAgreementKey, PinToken, PublicKey, SharedSecret};
+pub use extensions::ExtensionsInput;
+pub use get_assertion::{GetAssertion, GetAssertionReply, PubKeyCredDescriptor};
+pub use get_info::{GetInfo, GetInfoReply};
+pub use get_next_assertion::GetNextAssertion;
+pub use large_blobs::{LargeBlobs, LargeBlobsReply};
+pub use make_credential::{
+    AttStmt, AttStmtFormat, MakeCredential, MakeCredentialOptions, MakeCredentialReply,
+    PubKeyCredParam, Rp, User,
+};
+pub use reset::{Reset, ResetReply};
+pub use selection::{Selection, SelectionReply};
+
+/// A CTAP2 command: how to encode its `params` CBOR map.
+pub trait Ctap2Request {
+    type Reply: Ctap2Reply;
+    const CMD: u8;
+
+    fn params(&self) -> Option<Value>;
+}
+
+/// A CTAP2 reply: how to decode it from the response's `params` CBOR map
+/// (or its absence, for replies with no payload).
+pub trait Ctap2Reply: Sized {
+    fn parse(value: Option<Value>) -> Self;
+}
+
+impl Ctap2Reply for GetInfoReply {
+    fn parse(value: Option<Value>) -> Self {
+        GetInfoReply::parse(value)
+    }
+}
+
+impl Ctap2Reply for ClientPinReply {
+    fn parse(value: Option<Value>) -> Self {
+        ClientPinReply::parse(value)
+    }
+}
+
+impl Ctap2Reply for MakeCredentialReply {
+    fn parse(value: Option<Value>) -> Self {
+        MakeCredentialReply::parse(value)
+    }
+}
+
+impl Ctap2Reply for GetAssertionReply {
+    fn parse(value: Option<Value>) -> Self {
+        GetAssertionReply::parse(value)
+    }
+}
+
+impl Ctap2Reply for CredentialManagementReply {
+    fn parse(value: Option<Value>) -> Self {
+        CredentialManagementReply::parse(value)
+    }
+}
+
+impl Ctap2Reply for BioEnrollmentReply {
+    fn parse(value: Option<Value>) -> Self {
+        BioEnrollmentReply::parse(value)
+    }
+}
+
+impl Ctap2Reply for AuthenticatorConfigReply {
+    fn parse(value: Option<Value>) -> Self {
+        AuthenticatorConfigReply::parse(value)
+    }
+}
+
+impl Ctap2Reply for ResetReply {
+    fn parse(value: Option<Value>) -> Self {
+        ResetReply::parse(value)
+    }
+}
+
+impl Ctap2Reply for LargeBlobsReply {
+    fn parse(value: Option<Value>) -> Self {
+        LargeBlobsReply::parse(value)
+    }
+}
+
+impl Ctap2Reply for SelectionReply {
+    fn parse(value: Option<Value>) -> Self {
+        SelectionReply::parse(value)
+    }
+}
@@ -0,0 +1,28 @@
+//! `authenticatorReset` (0x07).
+
+use ciborium::Value;
+
+use super::Ctap2Request;
+
+/// `authenticatorReset` takes no parameters.
+pub struct Reset;
+
+impl Ctap2Request for Reset {
+    type Reply = ResetReply;
+    const CMD: u8 = 0x07;
+
+    fn params(&self) -> Option<Value> {
+        None
+    }
+}
+
+/// `authenticatorReset` has no response payload; success is the absence of
+/// an error status.
+#[derive(Debug, Default)]
+pub struct ResetReply;
+
+impl ResetReply {
+    pub(crate) fn parse(_value: Option<Value>) -> Self {
+        Self
+    }
+}
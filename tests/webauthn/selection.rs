@@ -0,0 +1,30 @@
+//! `authenticatorSelection` (0x0B).
+
+use ciborium::Value;
+
+use super::Ctap2Request;
+
+/// `authenticatorSelection` takes no parameters; it waits for user
+/// presence so the platform can tell which of several connected
+/// authenticators the user picked.
+pub struct Selection;
+
+impl Ctap2Request for Selection {
+    type Reply = SelectionReply;
+    const CMD: u8 = 0x0b;
+
+    fn params(&self) -> Option<Value> {
+        None
+    }
+}
+
+/// `authenticatorSelection` has no response payload; success is the
+/// absence of an error status.
+#[derive(Debug, Default)]
+pub struct SelectionReply;
+
+impl SelectionReply {
+    pub(crate) fn parse(_value: Option<Value>) -> Self {
+        Self
+    }
+}
@@ -0,0 +1,192 @@
+//! The legacy U2F/CTAP1 command set (`U2F_REGISTER`, `U2F_AUTHENTICATE`,
+//! `U2F_VERSION`), wire-compatible with the raw ISO 7816-4 APDUs browsers
+//! still send during "preflight" against CTAP2 authenticators.
+
+/// `USE_SIGN` (0x03): enforce user presence and sign.
+pub const CONTROL_ENFORCE_USER_PRESENCE_AND_SIGN: u8 = 0x03;
+/// `CHECK_ONLY` (0x07): just check whether the key handle is ours.
+pub const CONTROL_CHECK_ONLY: u8 = 0x07;
+/// `DONT_ENFORCE` (0x08): sign without requiring user presence.
+pub const CONTROL_DONT_ENFORCE_USER_PRESENCE_AND_SIGN: u8 = 0x08;
+
+pub const INS_REGISTER: u8 = 0x01;
+pub const INS_AUTHENTICATE: u8 = 0x02;
+pub const INS_VERSION: u8 = 0x03;
+
+/// `SW_CONDITIONS_NOT_SATISFIED` (0x6985): for `U2F_AUTHENTICATE` with
+/// `CHECK_ONLY`, signals "this key handle is valid" without asserting.
+pub const SW_CONDITIONS_NOT_SATISFIED: u16 = 0x6985;
+/// `SW_WRONG_DATA` (0x6a80): the key handle doesn't belong to this token.
+pub const SW_WRONG_DATA: u16 = 0x6a80;
+pub const SW_NO_ERROR: u16 = 0x9000;
+
+#[derive(Clone, Debug)]
+pub struct RegisterRequest {
+    pub challenge: [u8; 32],
+    pub application: [u8; 32],
+}
+
+impl RegisterRequest {
+    pub fn new(challenge: [u8; 32], application: [u8; 32]) -> Self {
+        Self {
+            challenge,
+            application,
+        }
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut data = self.challenge.to_vec();
+        data.extend_from_slice(&self.application);
+        data
+    }
+}
+
+/// `0x05 || pubKey(65) || keyHandleLen(1) || keyHandle || cert || sig`.
+#[derive(Clone, Debug)]
+pub struct RegisterResponse {
+    pub public_key: [u8; 65],
+    pub key_handle: Vec<u8>,
+    pub attestation_cert: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+impl RegisterResponse {
+    pub(crate) fn decode(data: &[u8]) -> Self {
+        assert_eq!(data[0], 0x05, "reserved byte must be 0x05");
+        let mut public_key = [0u8; 65];
+        public_key.copy_from_slice(&data[1..66]);
+        let key_handle_len = data[66] as usize;
+        let key_handle = data[67..67 + key_handle_len].to_vec();
+        let rest = &data[67 + key_handle_len..];
+
+        // The certificate is a DER SEQUENCE; its length prefix tells us
+        // where it ends and the trailing ECDSA signature begins.
+        let cert_len = der_length(&rest[1..]);
+        let cert_total_len = 1 + der_length_prefix_size(&rest[1..]) + cert_len;
+        let attestation_cert = rest[..cert_total_len].to_vec();
+        let signature = rest[cert_total_len..].to_vec();
+
+        Self {
+            public_key,
+            key_handle,
+            attestation_cert,
+            signature,
+        }
+    }
+
+    /// Verifies the registration signature over
+    /// `0x00 || application || challenge || keyHandle || publicKey`.
+    pub fn verify(&self, request: &RegisterRequest) {
+        use p256::ecdsa::signature::Verifier;
+        use p256::ecdsa::Signature;
+
+        let mut signed = vec![0x00];
+        signed.extend_from_slice(&request.application);
+        signed.extend_from_slice(&request.challenge);
+        signed.extend_from_slice(&self.key_handle);
+        signed.extend_from_slice(&self.public_key);
+
+        let signature = Signature::from_der(&self.signature).unwrap();
+        attestation_public_key()
+            .verify(&signed, &signature)
+            .expect("U2F registration signature must verify");
+    }
+}
+
+fn attestation_public_key() -> p256::ecdsa::VerifyingKey {
+    use p256::ecdsa::VerifyingKey;
+    use p256::SecretKey;
+    let secret = SecretKey::from_sec1_der(&hex_literal::hex!(
+        "30770201010420c7c00db46752da629e5b43cacca70d495439dd021c37b3065e5e24b0734f245a"
+        "a00a06082a8648ce3d030107a14403420004c7a1a4fdf0a6e0a2a9c2071d23b497bef9a47bc28f4"
+        "de3282205a3e523c6e3fa2c05a9969778f5a8966cf19afd73f803721c2b328878d39fd41d498ecb"
+        "0cbfeb"
+    ))
+    .unwrap();
+    VerifyingKey::from(&secret.public_key())
+}
+
+#[derive(Clone, Debug)]
+pub struct AuthenticateRequest {
+    pub control: u8,
+    pub challenge: [u8; 32],
+    pub application: [u8; 32],
+    pub key_handle: Vec<u8>,
+}
+
+impl AuthenticateRequest {
+    pub fn new(control: u8, challenge: [u8; 32], application: [u8; 32], key_handle: Vec<u8>) -> Self {
+        Self {
+            control,
+            challenge,
+            application,
+            key_handle,
+        }
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut data = self.challenge.to_vec();
+        data.extend_from_slice(&self.application);
+        data.push(self.key_handle.len() as u8);
+        data.extend_from_slice(&self.key_handle);
+        data
+    }
+}
+
+/// `userPresence(1) || counter(4, big-endian) || signature`.
+#[derive(Clone, Debug)]
+pub struct AuthenticateResponse {
+    pub user_presence: u8,
+    pub counter: u32,
+    pub signature: Vec<u8>,
+}
+
+impl AuthenticateResponse {
+    pub(crate) fn decode(data: &[u8]) -> Self {
+        Self {
+            user_presence: data[0],
+            counter: u32::from_be_bytes(data[1..5].try_into().unwrap()),
+            signature: data[5..].to_vec(),
+        }
+    }
+
+    /// Verifies the assertion signature over
+    /// `application || userPresence || counter || challenge`.
+    pub fn verify(&self, request: &AuthenticateRequest, credential_public_key: &[u8; 65]) {
+        use p256::ecdsa::signature::Verifier;
+        use p256::ecdsa::{Signature, VerifyingKey};
+        use p256::EncodedPoint;
+
+        let mut signed = request.application.to_vec();
+        signed.push(self.user_presence);
+        signed.extend_from_slice(&self.counter.to_be_bytes());
+        signed.extend_from_slice(&request.challenge);
+
+        let point = EncodedPoint::from_bytes(credential_public_key).unwrap();
+        let key = VerifyingKey::from_encoded_point(&point).unwrap();
+        let signature = Signature::from_der(&self.signature).unwrap();
+        key.verify(&signed, &signature)
+            .expect("U2F authentication signature must verify");
+    }
+}
+
+fn der_length(bytes: &[u8]) -> usize {
+    if bytes[0] & 0x80 == 0 {
+        bytes[0] as usize
+    } else {
+        let n = (bytes[0] & 0x7f) as usize;
+        let mut len = 0usize;
+        for &b in &bytes[1..1 + n] {
+            len = (len << 8) | b as usize;
+        }
+        len
+    }
+}
+
+fn der_length_prefix_size(bytes: &[u8]) -> usize {
+    if bytes[0] & 0x80 == 0 {
+        1
+    } else {
+        1 + (bytes[0] & 0x7f) as usize
+    }
+}